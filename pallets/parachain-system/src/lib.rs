@@ -177,6 +177,16 @@ where
 	check_version: bool,
 }
 
+/// A field of the [`PersistedValidationData`] given to `set_validation_data`.
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo, RuntimeDebug)]
+pub enum ValidationDataField {
+	/// The relay parent number is lower than the one of the previous block.
+	RelayParentNumber,
+	/// The relay parent storage root differs from the one of the previous block, although both
+	/// blocks are built on the same relay parent number.
+	RelayParentStorageRoot,
+}
+
 #[frame_support::pallet]
 pub mod pallet {
 	use super::*;
@@ -202,12 +212,32 @@ pub mod pallet {
 		/// The place where outbound XCMP messages come from. This is queried in `finalize_block`.
 		type OutboundXcmpMessageSource: XcmpMessageSource;
 
+		/// The maximum number of upward messages waiting to be sent to the relay chain.
+		///
+		/// Sending an upward message fails with [`MessageSendError::TooMany`] while this many are
+		/// pending. It also fails if the pending messages would exceed the relay chain's
+		/// `max_upward_queue_size` in total.
+		type MaxPendingUpwardMessages: Get<u32>;
+
 		/// The message handler that will be invoked when messages are received via DMP.
 		type DmpMessageHandler: DmpMessageHandler;
 
 		/// The weight we reserve at the beginning of the block for processing DMP messages.
 		type ReservedDmpWeight: Get<Weight>;
 
+		/// The weight charged for every received downward message on top of the weight reported
+		/// by the [`Config::DmpMessageHandler`].
+		///
+		/// Together with [`Config::DmpByteWeight`], this is deducted from the weight the handler
+		/// may use, so that the reserved DMP weight is respected.
+		type DmpBaseWeight: Get<Weight>;
+
+		/// The weight charged per byte of every received downward message on top of the weight
+		/// reported by the [`Config::DmpMessageHandler`].
+		///
+		/// This accounts for the decoding cost which grows with the size of a message.
+		type DmpByteWeight: Get<Weight>;
+
 		/// The message handler that will be invoked when messages are received via XCMP.
 		///
 		/// The messages are dispatched in the order they were relayed by the relay chain. If
@@ -218,9 +248,33 @@ pub mod pallet {
 		/// The weight we reserve at the beginning of the block for processing XCMP messages.
 		type ReservedXcmpWeight: Get<Weight>;
 
+		/// Whether a set `ReservedXcmpWeightOverride` should be cleared once a runtime upgrade is
+		/// applied, so that [`Config::ReservedXcmpWeight`] takes over again.
+		type ClearXcmpOverrideOnUpgrade: Get<bool>;
+
 		/// Something that can check the associated relay parent block number.
 		type CheckAssociatedRelayNumber: CheckAssociatedRelayNumber;
 
+		/// Whether to reject validation data that is built on the same relay parent as the
+		/// previous block.
+		///
+		/// This catches collators replaying the validation data of the previous block. It must
+		/// be `false` for parachains that build multiple blocks per relay parent, e.g. with
+		/// asynchronous backing enabled.
+		///
+		/// [`RelayNumberStrictlyIncreases`] already rejects any replayed relay parent, so this
+		/// only adds to the [`Config::CheckAssociatedRelayNumber`] implementations that accept
+		/// the same relay parent number twice. Enabling it costs a storage write per block.
+		type RejectRelayParentReplay: Get<bool>;
+
+		/// Whether to deposit [`Event::ValidationDataMismatch`] when the validation data diverges
+		/// from the one processed by the previous block.
+		///
+		/// This is a debugging aid for collators. The mismatch is logged as well, since the event
+		/// is lost if one of the checks on the validation data panics afterwards. This is always
+		/// the case with [`RelayNumberStrictlyIncreases`].
+		type ReportValidationDataMismatch: Get<bool>;
+
 		/// An entry-point for higher-level logic to manage the backlog of unincluded parachain
 		/// blocks and authorship rights for those blocks.
 		///
@@ -296,6 +350,24 @@ pub mod pallet {
 					},
 				};
 
+				// Drop the messages that exceed the relay chain's limits, e.g. because the host
+				// configuration changed since they were sent. They would otherwise hold back the
+				// messages queued after them forever.
+				let max_message_size = cmp::min(
+					host_config.max_upward_message_size,
+					host_config.max_upward_queue_size,
+				);
+				up.retain(|msg| {
+					let fits = msg.len() <= max_message_size as usize;
+					if !fits {
+						Self::deposit_event(Event::UpwardMessageDeliveryFailed {
+							message_hash: sp_io::hashing::blake2_256(msg),
+							reason: MessageSendError::TooBig,
+						});
+					}
+					fits
+				});
+
 				let available_capacity =
 					cmp::min(available_capacity, host_config.max_upward_message_num_per_candidate);
 
@@ -321,14 +393,24 @@ pub mod pallet {
 					.last()
 					.unwrap_or_default();
 
-				// TODO: #274 Return back messages that do not longer fit into the queue.
-
+				// The messages that don't fit into the relay dispatch queue right now stay pending.
+				// There are at most `MaxPendingUpwardMessages` of them.
 				UpwardMessages::<T>::put(&up[..num as usize]);
 				*up = up.split_off(num as usize);
+				<PendingUpwardMessagesSize<T>>::put(
+					up.iter().fold(0u32, |size, msg| size.saturating_add(msg.len() as u32)),
+				);
 
 				(num, total_size)
 			});
 
+			if ump_msg_count > 0 {
+				Self::deposit_event(Event::UpwardMessagesCommitted {
+					count: ump_msg_count,
+					bytes: ump_total_bytes,
+				});
+			}
+
 			// Sending HRMP messages is a little bit more involved. There are the following
 			// constraints:
 			//
@@ -466,6 +548,16 @@ pub mod pallet {
 			// Always try to read `UpgradeGoAhead` in `on_finalize`.
 			weight += T::DbWeight::get().reads(1);
 
+			// Weight for depositing `Event::UpwardMessagesCommitted` in `on_finalize`.
+			weight += T::DbWeight::get().writes(1);
+
+			// Weight for dropping the pending upward messages that exceed the relay chain's limits
+			// in `on_finalize`, one event each. The messages sent in this block were checked
+			// against these limits already.
+			let pending_upward_messages =
+				<PendingUpwardMessages<T>>::decode_len().unwrap_or_default() as u64;
+			weight += T::DbWeight::get().reads_writes(1, 1 + pending_upward_messages);
+
 			weight
 		}
 	}
@@ -510,12 +602,29 @@ pub mod pallet {
 				horizontal_messages,
 			} = data;
 
+			if T::ReportValidationDataMismatch::get() {
+				Self::report_validation_data_mismatch(&vfp);
+			}
+
 			// Check that the associated relay chain block number is as expected.
 			T::CheckAssociatedRelayNumber::check_associated_relay_number(
 				vfp.relay_parent_number,
 				LastRelayChainBlockNumber::<T>::get(),
 			);
+
+			// Check that the validation data isn't replayed from the previous block.
+			if T::RejectRelayParentReplay::get() &&
+				vfp.relay_parent_number <= LastRelayChainBlockNumber::<T>::get() &&
+				LastRelayParentStorageRoot::<T>::get() == Some(vfp.relay_parent_storage_root)
+			{
+				panic!("Validation data was already processed in a previous block!")
+			}
 			LastRelayChainBlockNumber::<T>::put(vfp.relay_parent_number);
+			// The storage root is only needed by the checks above, don't pay for it otherwise.
+			if T::RejectRelayParentReplay::get() || T::ReportValidationDataMismatch::get() {
+				LastRelayParentStorageRoot::<T>::put(vfp.relay_parent_storage_root);
+				total_weight += T::DbWeight::get().reads_writes(1, 1);
+			}
 
 			let relay_state_proof = RelayChainStateProof::new(
 				T::SelfParaId::get(),
@@ -568,6 +677,9 @@ pub mod pallet {
 					let validation_code = <PendingValidationCode<T>>::take();
 
 					Self::put_parachain_code(&validation_code);
+					if T::ClearXcmpOverrideOnUpgrade::get() {
+						<ReservedXcmpWeightOverride<T>>::kill();
+					}
 					<T::OnSystemEvent as OnSystemEvent>::on_validation_code_applied();
 					Self::deposit_event(Event::ValidationFunctionApplied {
 						relay_chain_block_num: vfp.relay_parent_number,
@@ -594,11 +706,19 @@ pub mod pallet {
 				.read_messaging_state_snapshot(&host_config)
 				.expect("Invalid messaging state in relay chain state proof");
 
+			// Don't reject the block if the proof lacks the session index.
+			match relay_state_proof.read_session_index() {
+				Ok(session_index) => <RelaySessionIndex<T>>::put(session_index),
+				Err(_) => <RelaySessionIndex<T>>::kill(),
+			}
+
 			<ValidationData<T>>::put(&vfp);
 			<RelayStateProof<T>>::put(relay_chain_state);
 			<RelevantMessagingState<T>>::put(relevant_messaging_state.clone());
 			<HostConfiguration<T>>::put(host_config);
 
+			total_weight += Self::maybe_schedule_deferred_upgrade(vfp.relay_parent_number);
+
 			<T::OnSystemEvent as OnSystemEvent>::on_validation_data(&vfp);
 
 			total_weight += Self::process_inbound_downward_messages(
@@ -667,6 +787,61 @@ pub mod pallet {
 			AuthorizedUpgrade::<T>::kill();
 			Ok(Pays::No.into())
 		}
+
+		/// Schedule an upgrade to `code` that is applied no earlier than the relay chain block
+		/// `earliest`.
+		///
+		/// If the relay chain's upgrade delay already ends at or after `earliest`, the upgrade is
+		/// scheduled right away. Otherwise it is deferred and scheduled by the first block for
+		/// which that is the case.
+		///
+		/// Like `System::set_code`, this ensures the spec name remains unchanged and that the spec
+		/// version has increased.
+		///
+		/// This call requires Root origin.
+		#[pallet::call_index(4)]
+		#[pallet::weight((1_000_000, DispatchClass::Operational))]
+		pub fn schedule_upgrade_at(
+			origin: OriginFor<T>,
+			code: Vec<u8>,
+			earliest: RelayChainBlockNumber,
+		) -> DispatchResult {
+			ensure_root(origin)?;
+			frame_system::Pallet::<T>::can_set_code(&code)?;
+
+			let vfp = Self::validation_data().ok_or(Error::<T>::ValidationDataNotAvailable)?;
+			let cfg =
+				Self::host_configuration().ok_or(Error::<T>::HostConfigurationNotAvailable)?;
+			if vfp.relay_parent_number.saturating_add(cfg.validation_upgrade_delay) >= earliest {
+				return Self::schedule_code_upgrade(code)
+			}
+
+			ensure!(!<PendingValidationCode<T>>::exists(), Error::<T>::OverlappingUpgrades);
+			ensure!(!<DeferredUpgradeAt<T>>::exists(), Error::<T>::OverlappingUpgrades);
+			ensure!(code.len() <= cfg.max_code_size as usize, Error::<T>::TooBig);
+
+			<DeferredUpgradeAt<T>>::put(earliest);
+			<DeferredValidationCode<T>>::put(code);
+			Self::deposit_event(Event::UpgradeDeferred { earliest });
+
+			Ok(())
+		}
+
+		/// Cancel the upgrade deferred by [`Pallet::schedule_upgrade_at`].
+		///
+		/// This call requires Root origin.
+		#[pallet::call_index(5)]
+		#[pallet::weight((1_000_000, DispatchClass::Operational))]
+		pub fn cancel_deferred_upgrade(origin: OriginFor<T>) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(<DeferredUpgradeAt<T>>::exists(), Error::<T>::NotScheduled);
+
+			<DeferredUpgradeAt<T>>::kill();
+			<DeferredValidationCode<T>>::kill();
+			Self::deposit_event(Event::DeferredUpgradeCancelled);
+
+			Ok(())
+		}
 	}
 
 	#[pallet::event]
@@ -680,12 +855,25 @@ pub mod pallet {
 		ValidationFunctionDiscarded,
 		/// An upgrade has been authorized.
 		UpgradeAuthorized { code_hash: T::Hash },
+		/// An upgrade has been deferred so that it is not applied before the given relay chain
+		/// block.
+		UpgradeDeferred { earliest: RelayChainBlockNumber },
+		/// The deferred upgrade has been cancelled.
+		DeferredUpgradeCancelled,
+		/// The deferred upgrade could not be scheduled and has been discarded.
+		DeferredUpgradeFailed { error: DispatchError },
 		/// Some downward messages have been received and will be processed.
 		DownwardMessagesReceived { count: u32 },
 		/// Downward messages were processed using the given weight.
 		DownwardMessagesProcessed { weight_used: Weight, dmq_head: relay_chain::Hash },
 		/// An upward message was sent to the relay chain.
 		UpwardMessageSent { message_hash: Option<XcmHash> },
+		/// Upward messages were committed to the candidate of this block.
+		UpwardMessagesCommitted { count: u32, bytes: u32 },
+		/// A pending upward message was dropped because it can no longer be delivered.
+		UpwardMessageDeliveryFailed { message_hash: XcmHash, reason: MessageSendError },
+		/// The validation data diverges from the one processed by the previous block.
+		ValidationDataMismatch { field: ValidationDataField },
 	}
 
 	#[pallet::error]
@@ -736,6 +924,20 @@ pub mod pallet {
 	#[pallet::getter(fn new_validation_function)]
 	pub(super) type PendingValidationCode<T: Config> = StorageValue<_, Vec<u8>, ValueQuery>;
 
+	/// The earliest relay chain block the upgrade deferred by [`Pallet::schedule_upgrade_at`] may
+	/// be applied at.
+	///
+	/// The upgrade is scheduled by the first block for which the relay chain's upgrade delay
+	/// ends at or after this block. This is kept apart from [`DeferredValidationCode`] so that
+	/// checking it every block doesn't add the code to the proof.
+	#[pallet::storage]
+	pub(super) type DeferredUpgradeAt<T: Config> =
+		StorageValue<_, RelayChainBlockNumber, OptionQuery>;
+
+	/// The validation code of the upgrade deferred by [`Pallet::schedule_upgrade_at`].
+	#[pallet::storage]
+	pub(super) type DeferredValidationCode<T: Config> = StorageValue<_, Vec<u8>, OptionQuery>;
+
 	/// Validation code that is set by the parachain and is to be communicated to collator and
 	/// consequently the relay-chain.
 	///
@@ -760,6 +962,13 @@ pub mod pallet {
 	pub(super) type LastRelayChainBlockNumber<T: Config> =
 		StorageValue<_, RelayChainBlockNumber, ValueQuery>;
 
+	/// The storage root of the relay parent associated with the last parachain block.
+	///
+	/// This is only tracked if [`Config::RejectRelayParentReplay`] or
+	/// [`Config::ReportValidationDataMismatch`] is enabled.
+	#[pallet::storage]
+	pub(super) type LastRelayParentStorageRoot<T: Config> = StorageValue<_, relay_chain::Hash>;
+
 	/// An option which indicates if the relay-chain restricts signalling a validation code upgrade.
 	/// In other words, if this is `Some` and [`NewValidationCode`] is `Some` then the produced
 	/// candidate will be invalid.
@@ -801,6 +1010,17 @@ pub mod pallet {
 	#[pallet::getter(fn relevant_messaging_state)]
 	pub(super) type RelevantMessagingState<T: Config> = StorageValue<_, MessagingStateSnapshot>;
 
+	/// The session index of the relay parent.
+	///
+	/// This field is meant to be updated each block with the validation data inherent. Therefore,
+	/// before processing of the inherent, e.g. in `on_initialize` this data may be stale.
+	///
+	/// This data is also absent from the genesis and if the relay chain state proof doesn't
+	/// contain the session index.
+	#[pallet::storage]
+	#[pallet::getter(fn relay_session_index)]
+	pub(super) type RelaySessionIndex<T: Config> = StorageValue<_, relay_chain::SessionIndex>;
+
 	/// The parachain host configuration that was obtained from the relay parent.
 	///
 	/// This field is meant to be updated each block with the validation data inherent. Therefore,
@@ -857,6 +1077,12 @@ pub mod pallet {
 	pub(super) type PendingUpwardMessages<T: Config> =
 		StorageValue<_, Vec<UpwardMessage>, ValueQuery>;
 
+	/// The total size of the [`PendingUpwardMessages`] in bytes.
+	///
+	/// This is recomputed in `on_finalize` of each block.
+	#[pallet::storage]
+	pub(super) type PendingUpwardMessagesSize<T: Config> = StorageValue<_, u32, ValueQuery>;
+
 	/// The number of HRMP messages we observed in `on_initialize` and thus used that number for
 	/// announcing the weight of `on_initialize` and `on_finalize`.
 	#[pallet::storage]
@@ -1077,13 +1303,24 @@ impl<T: Config> Pallet<T> {
 			let max_weight =
 				<ReservedDmpWeightOverride<T>>::get().unwrap_or_else(T::ReservedDmpWeight::get);
 
+			// The per message weight is taken from the reserved weight, so that the handler
+			// can't exceed it.
+			let total_bytes: u64 = downward_messages.iter().map(|m| m.msg.len() as u64).sum();
+			let message_weight = T::DmpBaseWeight::get()
+				.saturating_mul(dm_count as u64)
+				.saturating_add(T::DmpByteWeight::get().saturating_mul(total_bytes));
+			weight_used += message_weight;
+
 			let message_iter = downward_messages
 				.into_iter()
 				.inspect(|m| {
 					dmq_head.extend_downward(m);
 				})
 				.map(|m| (m.sent_at, m.msg));
-			weight_used += T::DmpMessageHandler::handle_dmp_messages(message_iter, max_weight);
+			weight_used += T::DmpMessageHandler::handle_dmp_messages(
+				message_iter,
+				max_weight.saturating_sub(message_weight),
+			);
 			<LastDmqMqcHead<T>>::put(&dmq_head);
 
 			Self::deposit_event(Event::DownwardMessagesProcessed {
@@ -1336,6 +1573,28 @@ impl<T: Config> Pallet<T> {
 		<HostConfiguration<T>>::get().map(|cfg| cfg.max_code_size)
 	}
 
+	/// Whether the `set_validation_data` inherent already ran in the current block.
+	pub fn did_set_validation_data() -> bool {
+		<ValidationData<T>>::exists()
+	}
+
+	/// The maximum PoV size of the current block, as given by the relay chain.
+	///
+	/// Returns `None` before the `set_validation_data` inherent ran in the current block.
+	pub fn max_pov_size() -> Option<u32> {
+		Self::validation_data().map(|vfp| vfp.max_pov_size)
+	}
+
+	/// The storage root of the relay parent the current block is built on.
+	///
+	/// The relay parent hash itself is not part of the [`PersistedValidationData`], so the
+	/// storage root is the closest commitment to the relay parent available on-chain. Proofs of
+	/// relay chain state should be verified against it. Returns `None` before the
+	/// `set_validation_data` inherent ran in the current block.
+	pub fn relay_parent_storage_root() -> Option<relay_chain::Hash> {
+		Self::validation_data().map(|vfp| vfp.relay_parent_storage_root)
+	}
+
 	/// The implementation of the runtime upgrade functionality for parachains.
 	pub fn schedule_code_upgrade(validation_function: Vec<u8>) -> DispatchResult {
 		// Ensure that `ValidationData` exists. We do not care about the validation data per se,
@@ -1345,6 +1604,7 @@ impl<T: Config> Pallet<T> {
 		ensure!(<UpgradeRestrictionSignal<T>>::get().is_none(), Error::<T>::ProhibitedByPolkadot);
 
 		ensure!(!<PendingValidationCode<T>>::exists(), Error::<T>::OverlappingUpgrades);
+		ensure!(!<DeferredUpgradeAt<T>>::exists(), Error::<T>::OverlappingUpgrades);
 		let cfg = Self::host_configuration().ok_or(Error::<T>::HostConfigurationNotAvailable)?;
 		ensure!(validation_function.len() <= cfg.max_code_size as usize, Error::<T>::TooBig);
 
@@ -1362,6 +1622,71 @@ impl<T: Config> Pallet<T> {
 		Ok(())
 	}
 
+	/// Log and deposit [`Event::ValidationDataMismatch`] for the first field of `vfp` that
+	/// diverges from the validation data processed by the previous block.
+	///
+	/// The event is rolled back if the [`Config::CheckAssociatedRelayNumber`] rejects `vfp`
+	/// afterwards, the log is not.
+	fn report_validation_data_mismatch(vfp: &PersistedValidationData) {
+		let last_relay_parent_number = LastRelayChainBlockNumber::<T>::get();
+		let last_relay_parent_storage_root = LastRelayParentStorageRoot::<T>::get();
+		let field = if vfp.relay_parent_number < last_relay_parent_number {
+			ValidationDataField::RelayParentNumber
+		} else if vfp.relay_parent_number == last_relay_parent_number &&
+			last_relay_parent_storage_root
+				.map_or(false, |root| root != vfp.relay_parent_storage_root)
+		{
+			ValidationDataField::RelayParentStorageRoot
+		} else {
+			return
+		};
+
+		log::warn!(
+			"Validation data mismatch in {:?}: relay parent number {} after {}, storage root {:?} \
+				after {:?}",
+			field,
+			vfp.relay_parent_number,
+			last_relay_parent_number,
+			vfp.relay_parent_storage_root,
+			last_relay_parent_storage_root,
+		);
+		Self::deposit_event(Event::ValidationDataMismatch { field });
+	}
+
+	/// Schedule the upgrade deferred by [`Pallet::schedule_upgrade_at`] once the relay chain's
+	/// upgrade delay ends at or after its earliest block.
+	fn maybe_schedule_deferred_upgrade(relay_parent_number: RelayChainBlockNumber) -> Weight {
+		let mut weight = T::DbWeight::get().reads(1);
+
+		let earliest = match <DeferredUpgradeAt<T>>::get() {
+			Some(earliest) => earliest,
+			None => return weight,
+		};
+		let upgrade_delay =
+			Self::host_configuration().map_or(0, |cfg| cfg.validation_upgrade_delay);
+		weight += T::DbWeight::get().reads(3);
+
+		// Wait for a pending upgrade to finish or for the relay chain to allow upgrades again.
+		if relay_parent_number.saturating_add(upgrade_delay) < earliest ||
+			<PendingValidationCode<T>>::exists() ||
+			<UpgradeRestrictionSignal<T>>::get().is_some()
+		{
+			return weight
+		}
+
+		// The code is only read by the block that schedules it.
+		<DeferredUpgradeAt<T>>::kill();
+		if let Some(code) = <DeferredValidationCode<T>>::take() {
+			if let Err(error) = Self::schedule_code_upgrade(code) {
+				log::warn!("Failed to schedule deferred upgrade: {:?}", error);
+				Self::deposit_event(Event::DeferredUpgradeFailed { error });
+			}
+		}
+		weight += T::DbWeight::get().reads_writes(4, 5);
+
+		weight
+	}
+
 	/// Returns the [`CollationInfo`] of the current active block.
 	///
 	/// The given `header` is the header of the built block we are collecting the collation info
@@ -1465,6 +1790,10 @@ impl<T: Config> frame_system::SetCode<T> for ParachainSetCode<T> {
 }
 
 impl<T: Config> Pallet<T> {
+	/// Queue `message` to be sent to the relay chain.
+	///
+	/// Returns the position of the message among the pending upward messages and its hash. The
+	/// position is only accurate until `on_finalize`, which sends and drops pending messages.
 	pub fn send_upward_message(message: UpwardMessage) -> Result<(u32, XcmHash), MessageSendError> {
 		// Check if the message fits into the relay-chain constraints.
 		//
@@ -1478,10 +1807,16 @@ impl<T: Config> Pallet<T> {
 		// may change so that the message is no longer valid.
 		//
 		// However, changing this setting is expected to be rare.
+		let pending_size =
+			<PendingUpwardMessagesSize<T>>::get().saturating_add(message.len() as u32);
 		if let Some(cfg) = Self::host_configuration() {
 			if message.len() > cfg.max_upward_message_size as usize {
 				return Err(MessageSendError::TooBig)
 			}
+			// More than that can't be sent at once anyway.
+			if pending_size > cfg.max_upward_queue_size {
+				return Err(MessageSendError::TooMany)
+			}
 		} else {
 			// This storage field should carry over from the previous block. So if it's None
 			// then it must be that this is an edge-case where a message is attempted to be
@@ -1493,13 +1828,54 @@ impl<T: Config> Pallet<T> {
 			//
 			// Thus fall through here.
 		};
+
+		let position = <PendingUpwardMessages<T>>::decode_len().unwrap_or_default() as u32;
+		if position >= T::MaxPendingUpwardMessages::get() {
+			return Err(MessageSendError::TooMany)
+		}
 		<PendingUpwardMessages<T>>::append(message.clone());
+		<PendingUpwardMessagesSize<T>>::put(pending_size);
 
 		// The relay ump does not use using_encoded
 		// We apply the same this to use the same hash
 		let hash = sp_io::hashing::blake2_256(&message);
 		Self::deposit_event(Event::UpwardMessageSent { message_hash: Some(hash) });
-		Ok((0, hash))
+		Ok((position, hash))
+	}
+
+	/// Check whether an HRMP message of `len` bytes could be sent to `recipient`.
+	///
+	/// Returns [`MessageSendError::TooBig`] if the message exceeds the channel's maximum message
+	/// size. Otherwise returns [`ChannelStatus::Closed`] if there is no channel to `recipient`,
+	/// [`ChannelStatus::Full`] if the message doesn't fit into the channel right now and
+	/// [`ChannelStatus::Ready`] if it does.
+	///
+	/// Like [`GetChannelInfo::get_channel_status`], this may use the messaging state of the
+	/// previous block, so it is only a preliminary check that allows rejecting messages before
+	/// they are enqueued.
+	pub fn check_hrmp_message_size(
+		recipient: ParaId,
+		len: usize,
+	) -> Result<ChannelStatus, MessageSendError> {
+		match Self::get_channel_max(recipient) {
+			Some(max_message_size) if len > max_message_size => Err(MessageSendError::TooBig),
+			Some(_) => match Self::get_channel_status(recipient) {
+				ChannelStatus::Ready(max_size_now, _) if len > max_size_now =>
+					Ok(ChannelStatus::Full),
+				status => Ok(status),
+			},
+			None => Ok(ChannelStatus::Closed),
+		}
+	}
+
+	/// The number of messages that can still be sent over the egress channel to `id`.
+	///
+	/// Returns `None` if there is no channel to `id`.
+	pub fn get_channel_remaining_capacity(id: ParaId) -> Option<u32> {
+		let channels = Self::relevant_messaging_state()?.egress_channels;
+		let index = channels.binary_search_by_key(&id, |item| item.0).ok()?;
+		let meta = &channels[index].1;
+		Some(meta.max_capacity.saturating_sub(meta.msg_count))
 	}
 }
 
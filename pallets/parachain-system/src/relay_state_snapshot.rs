@@ -19,6 +19,7 @@
 use codec::{Decode, Encode};
 use cumulus_primitives_core::{
 	relay_chain, AbridgedHostConfiguration, AbridgedHrmpChannel, ParaId,
+	RELAY_CURRENT_SESSION_INDEX_KEY,
 };
 use scale_info::TypeInfo;
 use sp_runtime::traits::HashingFor;
@@ -80,6 +81,8 @@ pub enum Error {
 	ReadOptionalEntry(ReadEntryErr),
 	/// The slot cannot be extracted.
 	Slot(ReadEntryErr),
+	/// The session index cannot be extracted.
+	SessionIndex(ReadEntryErr),
 	/// The upgrade go-ahead signal cannot be read.
 	UpgradeGoAhead(ReadEntryErr),
 	/// The upgrade restriction signal cannot be read.
@@ -303,6 +306,17 @@ impl RelayChainStateProof {
 			.map_err(Error::Slot)
 	}
 
+	/// Read the current [`SessionIndex`](relay_chain::SessionIndex) from the relay chain state
+	/// proof.
+	///
+	/// The session index is the one of the relay chain block this state proof was extracted from.
+	///
+	/// Returns an error if anything failed at reading or decoding.
+	pub fn read_session_index(&self) -> Result<relay_chain::SessionIndex, Error> {
+		read_entry(&self.trie_backend, RELAY_CURRENT_SESSION_INDEX_KEY, None)
+			.map_err(Error::SessionIndex)
+	}
+
 	/// Read the go-ahead signal for the upgrade from the relay chain state proof.
 	///
 	/// The go-ahead specifies whether the parachain can apply the upgrade or should abort it. If
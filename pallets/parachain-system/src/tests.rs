@@ -72,6 +72,12 @@ parameter_types! {
 	pub const ParachainId: ParaId = ParaId::new(200);
 	pub const ReservedXcmpWeight: Weight = Weight::zero();
 	pub const ReservedDmpWeight: Weight = Weight::zero();
+	pub const DmpBaseWeight: Weight = Weight::from_parts(1_000, 0);
+	pub const DmpByteWeight: Weight = Weight::from_parts(10, 1);
+	pub static ClearXcmpOverrideOnUpgrade: bool = false;
+	pub static RejectRelayParentReplay: bool = false;
+	pub static ReportValidationDataMismatch: bool = false;
+	pub static MaxPendingUpwardMessages: u32 = 1024;
 }
 impl frame_system::Config for Test {
 	type RuntimeOrigin = RuntimeOrigin;
@@ -103,11 +109,17 @@ impl Config for Test {
 	type OnSystemEvent = ();
 	type SelfParaId = ParachainId;
 	type OutboundXcmpMessageSource = FromThreadLocal;
+	type MaxPendingUpwardMessages = MaxPendingUpwardMessages;
 	type DmpMessageHandler = SaveIntoThreadLocal;
 	type ReservedDmpWeight = ReservedDmpWeight;
+	type DmpBaseWeight = DmpBaseWeight;
+	type DmpByteWeight = DmpByteWeight;
 	type XcmpMessageHandler = SaveIntoThreadLocal;
 	type ReservedXcmpWeight = ReservedXcmpWeight;
+	type ClearXcmpOverrideOnUpgrade = ClearXcmpOverrideOnUpgrade;
 	type CheckAssociatedRelayNumber = AnyRelayNumber;
+	type RejectRelayParentReplay = RejectRelayParentReplay;
+	type ReportValidationDataMismatch = ReportValidationDataMismatch;
 	type ConsensusHook = TestConsensusHook;
 }
 
@@ -118,6 +130,7 @@ std::thread_local! {
 	static HANDLED_DMP_MESSAGES: RefCell<Vec<(relay_chain::BlockNumber, Vec<u8>)>> = RefCell::new(Vec::new());
 	static HANDLED_XCMP_MESSAGES: RefCell<Vec<(ParaId, relay_chain::BlockNumber, Vec<u8>)>> = RefCell::new(Vec::new());
 	static SENT_MESSAGES: RefCell<Vec<(ParaId, Vec<u8>)>> = RefCell::new(Vec::new());
+	static DMP_MAX_WEIGHT: RefCell<Weight> = RefCell::new(Weight::zero());
 	static CONSENSUS_HOOK: RefCell<Box<dyn Fn(&RelayChainStateProof) -> (Weight, UnincludedSegmentCapacity)>>
 		= RefCell::new(Box::new(|_| (Weight::zero(), NonZeroU32::new(1).unwrap().into())));
 }
@@ -173,8 +186,9 @@ impl XcmpMessageSource for FromThreadLocal {
 impl DmpMessageHandler for SaveIntoThreadLocal {
 	fn handle_dmp_messages(
 		iter: impl Iterator<Item = (RelayBlockNumber, Vec<u8>)>,
-		_max_weight: Weight,
+		max_weight: Weight,
 	) -> Weight {
+		DMP_MAX_WEIGHT.with(|w| *w.borrow_mut() = max_weight);
 		HANDLED_DMP_MESSAGES.with(|m| {
 			for i in iter {
 				m.borrow_mut().push(i);
@@ -448,6 +462,111 @@ fn block_tests_run_on_drop() {
 	BlockTests::new().add(123, || panic!("if this test passes, block tests run properly"));
 }
 
+#[test]
+fn monotonically_increasing_relay_number_accepts_equal() {
+	RelayNumberMonotonicallyIncreases::check_associated_relay_number(5, 4);
+	RelayNumberMonotonicallyIncreases::check_associated_relay_number(5, 5);
+}
+
+#[test]
+#[should_panic = "Relay chain block number needs to monotonically increase"]
+fn monotonically_increasing_relay_number_rejects_decreasing() {
+	RelayNumberMonotonicallyIncreases::check_associated_relay_number(4, 5);
+}
+
+#[test]
+#[should_panic = "Relay chain block number needs to strictly increase"]
+fn strictly_increasing_relay_number_rejects_equal() {
+	RelayNumberStrictlyIncreases::check_associated_relay_number(5, 5);
+}
+
+fn validation_data_mismatches() -> Vec<ValidationDataField> {
+	System::events()
+		.into_iter()
+		.filter_map(|r| match r.event {
+			RuntimeEvent::ParachainSystem(crate::Event::ValidationDataMismatch { field }) =>
+				Some(field),
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn validation_data_mismatch_is_reported() {
+	ReportValidationDataMismatch::set(true);
+
+	BlockTests::new()
+		.with_relay_block_number(|n| if *n == 2 { 4 } else { 5 })
+		.add_with_post_test(1, || {}, || assert!(validation_data_mismatches().is_empty()))
+		.add_with_post_test(
+			2,
+			|| {},
+			|| {
+				assert_eq!(
+					validation_data_mismatches(),
+					vec![ValidationDataField::RelayParentNumber]
+				)
+			},
+		);
+}
+
+#[test]
+fn relay_parent_storage_root_mismatch_is_reported() {
+	ReportValidationDataMismatch::set(true);
+
+	// The relay parent number stays the same, while the storage root changes with the included
+	// para head.
+	BlockTests::new()
+		.with_relay_block_number(|_| 5)
+		.add_with_post_test(1, || {}, || assert!(validation_data_mismatches().is_empty()))
+		.add_with_post_test(
+			2,
+			|| {},
+			|| {
+				assert_eq!(
+					validation_data_mismatches(),
+					vec![ValidationDataField::RelayParentStorageRoot]
+				)
+			},
+		);
+}
+
+#[test]
+#[should_panic = "Validation data was already processed in a previous block!"]
+fn replayed_relay_parent_is_rejected() {
+	RejectRelayParentReplay::set(true);
+	CONSENSUS_HOOK.with(|c| *c.borrow_mut() = Box::new(ExpectParentIncluded::on_state_proof));
+
+	BlockTests::new()
+		.with_relay_block_number(|_| 10)
+		.with_relay_sproof_builder(|_, _, sproof| {
+			// Make the relay parent storage root the same for every block, the parent is
+			// expected to be included anyway.
+			sproof.included_para_head = None;
+		})
+		.add(1, || {})
+		.add(2, || {});
+}
+
+#[test]
+fn replayed_relay_parent_is_accepted_by_default() {
+	CONSENSUS_HOOK.with(|c| *c.borrow_mut() = Box::new(ExpectParentIncluded::on_state_proof));
+
+	BlockTests::new()
+		.with_relay_block_number(|_| 10)
+		.with_relay_sproof_builder(|_, _, sproof| {
+			sproof.included_para_head = None;
+		})
+		.add(1, || {})
+		.add_with_post_test(
+			2,
+			|| {},
+			|| {
+				assert!(<LastRelayParentStorageRoot<Test>>::get().is_none());
+			},
+		);
+}
+
 #[test]
 fn test_xcmp_source_keeps_messages() {
 	let recipient = ParaId::from(400);
@@ -489,6 +608,53 @@ fn test_xcmp_source_keeps_messages() {
 		);
 }
 
+#[test]
+fn check_hrmp_message_size_respects_channel_limits() {
+	let recipient = ParaId::from(400);
+
+	BlockTests::new()
+		.with_relay_sproof_builder(move |_, _, sproof| {
+			let channel = sproof.upsert_outbound_channel(recipient);
+			channel.max_capacity = 1;
+			channel.max_total_size = 4;
+			channel.max_message_size = 8;
+		})
+		.add(1, move || {
+			assert!(matches!(
+				ParachainSystem::check_hrmp_message_size(recipient, 4),
+				Ok(ChannelStatus::Ready(4, 8))
+			));
+			assert!(matches!(
+				ParachainSystem::check_hrmp_message_size(recipient, 6),
+				Ok(ChannelStatus::Full)
+			));
+			assert!(matches!(
+				ParachainSystem::check_hrmp_message_size(recipient, 9),
+				Err(MessageSendError::TooBig)
+			));
+			assert!(matches!(
+				ParachainSystem::check_hrmp_message_size(ParaId::from(500), 1),
+				Ok(ChannelStatus::Closed)
+			));
+		});
+}
+
+#[test]
+fn get_channel_remaining_capacity_works() {
+	let recipient = ParaId::from(400);
+
+	BlockTests::new()
+		.with_relay_sproof_builder(move |_, _, sproof| {
+			let channel = sproof.upsert_outbound_channel(recipient);
+			channel.max_capacity = 5;
+			channel.msg_count = 2;
+		})
+		.add(1, move || {
+			assert_eq!(ParachainSystem::get_channel_remaining_capacity(recipient), Some(3));
+			assert_eq!(ParachainSystem::get_channel_remaining_capacity(ParaId::from(500)), None);
+		});
+}
+
 #[test]
 fn unincluded_segment_works() {
 	CONSENSUS_HOOK.with(|c| {
@@ -905,6 +1071,53 @@ fn events() {
 		);
 }
 
+#[test]
+fn xcmp_weight_override_is_cleared_on_upgrade() {
+	ClearXcmpOverrideOnUpgrade::set(true);
+
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, block_number, builder| {
+			if block_number > 123 {
+				builder.upgrade_go_ahead = Some(relay_chain::UpgradeGoAhead::GoAhead);
+			}
+		})
+		.add(123, || {
+			<ReservedXcmpWeightOverride<Test>>::put(Weight::from_parts(1_000, 0));
+			assert_ok!(System::set_code(RawOrigin::Root.into(), Default::default()));
+		})
+		.add_with_post_test(
+			1234,
+			|| {},
+			|| {
+				assert!(<ReservedXcmpWeightOverride<Test>>::get().is_none());
+			},
+		);
+}
+
+#[test]
+fn xcmp_weight_override_is_kept_on_upgrade_by_default() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, block_number, builder| {
+			if block_number > 123 {
+				builder.upgrade_go_ahead = Some(relay_chain::UpgradeGoAhead::GoAhead);
+			}
+		})
+		.add(123, || {
+			<ReservedXcmpWeightOverride<Test>>::put(Weight::from_parts(1_000, 0));
+			assert_ok!(System::set_code(RawOrigin::Root.into(), Default::default()));
+		})
+		.add_with_post_test(
+			1234,
+			|| {},
+			|| {
+				assert_eq!(
+					<ReservedXcmpWeightOverride<Test>>::get(),
+					Some(Weight::from_parts(1_000, 0))
+				);
+			},
+		);
+}
+
 #[test]
 fn non_overlapping() {
 	BlockTests::new()
@@ -922,6 +1135,129 @@ fn non_overlapping() {
 		});
 }
 
+#[test]
+fn schedule_upgrade_at_defers_until_earliest_block() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, _, builder| {
+			builder.host_config.validation_upgrade_delay = 10;
+		})
+		.add(123, || {
+			assert_ok!(ParachainSystem::schedule_upgrade_at(
+				RawOrigin::Root.into(),
+				vec![1, 2, 3],
+				1000
+			));
+			assert!(!<PendingValidationCode<Test>>::exists());
+			assert_eq!(<DeferredUpgradeAt<Test>>::get(), Some(1000));
+			assert_eq!(<DeferredValidationCode<Test>>::get(), Some(vec![1, 2, 3]));
+			assert_eq!(
+				System::set_code(RawOrigin::Root.into(), Default::default()),
+				Err(Error::<Test>::OverlappingUpgrades.into()),
+			);
+		})
+		.add(989, || {
+			assert!(!<PendingValidationCode<Test>>::exists());
+			assert!(<DeferredUpgradeAt<Test>>::exists());
+		})
+		.add(990, || {
+			assert_eq!(<PendingValidationCode<Test>>::get(), vec![1, 2, 3]);
+			assert!(!<DeferredUpgradeAt<Test>>::exists());
+			assert!(!<DeferredValidationCode<Test>>::exists());
+		});
+}
+
+#[test]
+fn schedule_upgrade_at_checks_runtime_version() {
+	let version = RuntimeVersion {
+		spec_name: "test".into(),
+		spec_version: 1,
+		impl_version: 1,
+		..Default::default()
+	};
+
+	let mut ext = new_test_ext();
+	ext.register_extension(sp_core::traits::ReadRuntimeVersionExt::new(ReadRuntimeVersion(
+		version.encode(),
+	)));
+	ext.execute_with(|| {
+		assert_eq!(
+			ParachainSystem::schedule_upgrade_at(RawOrigin::Root.into(), vec![1, 2, 3], 1000),
+			Err(frame_system::Error::<Test>::SpecVersionNeedsToIncrease.into()),
+		);
+		assert!(!<DeferredUpgradeAt<Test>>::exists());
+	});
+}
+
+#[test]
+fn deferred_upgrade_can_be_cancelled() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, _, builder| {
+			builder.host_config.validation_upgrade_delay = 10;
+		})
+		.add(123, || {
+			assert_eq!(
+				ParachainSystem::cancel_deferred_upgrade(RawOrigin::Root.into()),
+				Err(Error::<Test>::NotScheduled.into()),
+			);
+			assert_ok!(ParachainSystem::schedule_upgrade_at(
+				RawOrigin::Root.into(),
+				vec![1, 2, 3],
+				1000
+			));
+			assert_ok!(ParachainSystem::cancel_deferred_upgrade(RawOrigin::Root.into()));
+			assert!(!<DeferredUpgradeAt<Test>>::exists());
+			assert!(!<DeferredValidationCode<Test>>::exists());
+			assert_ok!(System::set_code(RawOrigin::Root.into(), Default::default()));
+		});
+}
+
+#[test]
+fn deferred_upgrade_failure_is_reported() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, block_number, builder| {
+			builder.host_config.validation_upgrade_delay = 10;
+			if block_number == 990 {
+				builder.host_config.max_code_size = 2;
+			}
+		})
+		.add(123, || {
+			assert_ok!(ParachainSystem::schedule_upgrade_at(
+				RawOrigin::Root.into(),
+				vec![1, 2, 3],
+				1000
+			));
+		})
+		.add_with_post_test(
+			990,
+			|| {},
+			|| {
+				assert!(!<PendingValidationCode<Test>>::exists());
+				assert!(!<DeferredValidationCode<Test>>::exists());
+				assert!(System::events().iter().any(|r| r.event ==
+					RuntimeEvent::ParachainSystem(crate::Event::DeferredUpgradeFailed {
+						error: Error::<Test>::TooBig.into()
+					})));
+			},
+		);
+}
+
+#[test]
+fn schedule_upgrade_at_within_upgrade_delay_schedules_right_away() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, _, builder| {
+			builder.host_config.validation_upgrade_delay = 10;
+		})
+		.add(123, || {
+			assert_ok!(ParachainSystem::schedule_upgrade_at(
+				RawOrigin::Root.into(),
+				vec![1, 2, 3],
+				130
+			));
+			assert_eq!(<PendingValidationCode<Test>>::get(), vec![1, 2, 3]);
+			assert!(!<DeferredUpgradeAt<Test>>::exists());
+		});
+}
+
 #[test]
 fn manipulates_storage() {
 	BlockTests::new()
@@ -1057,6 +1393,193 @@ fn send_upward_message_relay_bottleneck() {
 		);
 }
 
+#[test]
+fn send_upward_message_returns_queue_position() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, relay_block_num, sproof| match relay_block_num {
+			1 => sproof.relay_dispatch_queue_remaining_capacity = Some((0, 2048)),
+			_ => sproof.relay_dispatch_queue_remaining_capacity = Some((1, 2048)),
+		})
+		.add(1, || {
+			for i in 0..3 {
+				let (position, _) = ParachainSystem::send_upward_message(vec![i; 8]).unwrap();
+				assert_eq!(position, i as u32);
+			}
+		})
+		.add(2, || {
+			// Nothing was sent in the previous block.
+			let (position, _) = ParachainSystem::send_upward_message(vec![3; 8]).unwrap();
+			assert_eq!(position, 3);
+		})
+		.add(3, || {
+			// One message was sent in the previous block.
+			let (position, _) = ParachainSystem::send_upward_message(vec![4; 8]).unwrap();
+			assert_eq!(position, 3);
+		});
+}
+
+#[test]
+fn send_upward_message_fails_with_too_many_pending() {
+	MaxPendingUpwardMessages::set(2);
+
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, relay_block_num, sproof| match relay_block_num {
+			1 => sproof.relay_dispatch_queue_remaining_capacity = Some((0, 2048)),
+			_ => sproof.relay_dispatch_queue_remaining_capacity = Some((1, 2048)),
+		})
+		.add_with_post_test(
+			1,
+			|| {
+				assert_ok!(ParachainSystem::send_upward_message(vec![0; 8]));
+				assert_ok!(ParachainSystem::send_upward_message(vec![1; 8]));
+				assert_eq!(
+					ParachainSystem::send_upward_message(vec![2; 8]),
+					Err(MessageSendError::TooMany)
+				);
+			},
+			|| {
+				assert!(UpwardMessages::<Test>::get().is_empty());
+				assert_eq!(PendingUpwardMessages::<Test>::get().len(), 2);
+			},
+		)
+		.add_with_post_test(
+			2,
+			|| {
+				assert_eq!(
+					ParachainSystem::send_upward_message(vec![2; 8]),
+					Err(MessageSendError::TooMany)
+				);
+			},
+			|| {
+				assert_eq!(UpwardMessages::<Test>::get(), vec![vec![0; 8]]);
+			},
+		)
+		.add_with_post_test(
+			3,
+			|| {
+				// Sending the first message made room for another one.
+				assert_ok!(ParachainSystem::send_upward_message(vec![2; 8]));
+			},
+			|| {
+				assert_eq!(UpwardMessages::<Test>::get(), vec![vec![1; 8]]);
+				assert_eq!(PendingUpwardMessages::<Test>::get(), vec![vec![2; 8]]);
+			},
+		);
+}
+
+#[test]
+fn send_upward_message_fails_when_pending_messages_exceed_queue_size() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, relay_block_num, sproof| {
+			sproof.host_config.max_upward_queue_size = 20;
+			match relay_block_num {
+				1 => sproof.relay_dispatch_queue_remaining_capacity = Some((0, 2048)),
+				_ => sproof.relay_dispatch_queue_remaining_capacity = Some((1, 2048)),
+			}
+		})
+		.add_with_post_test(
+			1,
+			|| {
+				assert_ok!(ParachainSystem::send_upward_message(vec![0; 8]));
+				assert_ok!(ParachainSystem::send_upward_message(vec![1; 8]));
+				assert_eq!(
+					ParachainSystem::send_upward_message(vec![2; 8]),
+					Err(MessageSendError::TooMany)
+				);
+			},
+			|| {
+				assert_eq!(PendingUpwardMessagesSize::<Test>::get(), 16);
+			},
+		)
+		.add_with_post_test(
+			2,
+			|| {},
+			|| {
+				assert_eq!(UpwardMessages::<Test>::get(), vec![vec![0; 8]]);
+				assert_eq!(PendingUpwardMessagesSize::<Test>::get(), 8);
+			},
+		);
+}
+
+#[test]
+fn upward_message_exceeding_relay_limits_is_dropped() {
+	let big_message = vec![0u8; 200];
+	let big_message_hash = blake2_256(&big_message);
+
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, relay_block_num, sproof| match relay_block_num {
+			1 => sproof.relay_dispatch_queue_remaining_capacity = Some((0, 2048)),
+			_ => {
+				sproof.relay_dispatch_queue_remaining_capacity = Some((2, 2048));
+				// The relay chain lowered the limit after the message was sent.
+				sproof.host_config.max_upward_message_size = 100;
+			},
+		})
+		.add_with_post_test(
+			1,
+			move || {
+				assert_ok!(ParachainSystem::send_upward_message(big_message.clone()));
+				assert_ok!(ParachainSystem::send_upward_message(vec![1u8; 8]));
+			},
+			|| {
+				assert!(UpwardMessages::<Test>::get().is_empty());
+			},
+		)
+		.add_with_post_test(
+			2,
+			|| {},
+			move || {
+				assert_eq!(UpwardMessages::<Test>::get(), vec![vec![1u8; 8]]);
+				assert!(PendingUpwardMessages::<Test>::get().is_empty());
+				assert!(System::events().iter().any(|r| r.event ==
+					RuntimeEvent::ParachainSystem(crate::Event::UpwardMessageDeliveryFailed {
+						message_hash: big_message_hash,
+						reason: MessageSendError::TooBig,
+					})));
+			},
+		);
+}
+
+#[test]
+fn upward_messages_committed_event_reflects_committed_messages() {
+	fn upward_messages_committed() -> Option<(u32, u32)> {
+		System::events().into_iter().find_map(|r| match r.event {
+			RuntimeEvent::ParachainSystem(crate::Event::UpwardMessagesCommitted {
+				count,
+				bytes,
+			}) => Some((count, bytes)),
+			_ => None,
+		})
+	}
+
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, _, sproof| {
+			sproof.host_config.max_upward_message_num_per_candidate = 5;
+			sproof.relay_dispatch_queue_remaining_capacity = Some((2, 2048));
+		})
+		.add_with_post_test(
+			1,
+			|| {
+				for i in 0..4 {
+					ParachainSystem::send_upward_message(vec![i; 8]).unwrap();
+				}
+			},
+			|| {
+				assert_eq!(UpwardMessages::<Test>::get().len(), 2);
+				assert_eq!(upward_messages_committed(), Some((2, 16)));
+			},
+		)
+		.add_with_post_test(
+			2,
+			|| {},
+			|| {
+				assert_eq!(UpwardMessages::<Test>::get(), vec![vec![2u8; 8], vec![3u8; 8]]);
+				assert_eq!(upward_messages_committed(), Some((2, 16)));
+			},
+		)
+		.add_with_post_test(3, || {}, || assert_eq!(upward_messages_committed(), None));
+}
+
 #[test]
 fn send_hrmp_message_buffer_channel_close() {
 	BlockTests::new()
@@ -1175,6 +1698,43 @@ fn message_queue_chain() {
 	);
 }
 
+#[test]
+fn compute_mqc_head_matches_message_queue_chain() {
+	use cumulus_primitives_parachain_inherent::{compute_dmq_mqc_head, compute_hrmp_mqc_head};
+
+	assert_eq!(compute_dmq_mqc_head(H256::zero(), &[]), H256::zero());
+	assert_eq!(
+		compute_dmq_mqc_head(
+			H256::zero(),
+			&[
+				InboundDownwardMessage { sent_at: 2, msg: vec![1, 2, 3] },
+				InboundDownwardMessage { sent_at: 3, msg: vec![4, 5, 6] },
+			],
+		),
+		hex!["88dc00db8cc9d22aa62b87807705831f164387dfa49f80a8600ed1cbe1704b6b"].into(),
+	);
+	assert_eq!(
+		compute_hrmp_mqc_head(
+			H256::zero(),
+			&[
+				InboundHrmpMessage { sent_at: 2, data: vec![1, 2, 3] },
+				InboundHrmpMessage { sent_at: 3, data: vec![4, 5, 6] },
+			],
+		),
+		hex!["88dc00db8cc9d22aa62b87807705831f164387dfa49f80a8600ed1cbe1704b6b"].into(),
+	);
+
+	// Extending from an intermediate head yields the same result as one pass.
+	let first = compute_dmq_mqc_head(
+		H256::zero(),
+		&[InboundDownwardMessage { sent_at: 2, msg: vec![1, 2, 3] }],
+	);
+	assert_eq!(
+		compute_dmq_mqc_head(first, &[InboundDownwardMessage { sent_at: 3, msg: vec![4, 5, 6] }]),
+		hex!["88dc00db8cc9d22aa62b87807705831f164387dfa49f80a8600ed1cbe1704b6b"].into(),
+	);
+}
+
 #[test]
 fn receive_dmp() {
 	lazy_static::lazy_static! {
@@ -1270,6 +1830,76 @@ fn receive_dmp_after_pause() {
 		});
 }
 
+#[test]
+fn receive_dmp_weight_scales_with_message_size() {
+	lazy_static::lazy_static! {
+		static ref MSG_1: InboundDownwardMessage = InboundDownwardMessage {
+			sent_at: 1,
+			msg: b"down".to_vec(),
+		};
+		static ref MSG_2: InboundDownwardMessage = InboundDownwardMessage {
+			sent_at: 2,
+			msg: vec![0u8; 100],
+		};
+	}
+
+	fn dmp_weight_used() -> Weight {
+		System::events()
+			.into_iter()
+			.find_map(|r| match r.event {
+				RuntimeEvent::ParachainSystem(crate::Event::DownwardMessagesProcessed {
+					weight_used,
+					..
+				}) => Some(weight_used),
+				_ => None,
+			})
+			.expect("downward messages were processed")
+	}
+
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, relay_block_num, sproof| match relay_block_num {
+			1 => {
+				sproof.dmq_mqc_head =
+					Some(MessageQueueChain::default().extend_downward(&MSG_1).head());
+			},
+			2 => {
+				sproof.dmq_mqc_head = Some(
+					MessageQueueChain::default()
+						.extend_downward(&MSG_1)
+						.extend_downward(&MSG_2)
+						.head(),
+				);
+			},
+			_ => unreachable!(),
+		})
+		.with_inherent_data(|_, relay_block_num, data| match relay_block_num {
+			1 => {
+				data.downward_messages.push(MSG_1.clone());
+			},
+			2 => {
+				data.downward_messages.push(MSG_2.clone());
+			},
+			_ => unreachable!(),
+		})
+		.add(1, || {
+			assert_eq!(
+				dmp_weight_used(),
+				DmpBaseWeight::get().saturating_add(DmpByteWeight::get().saturating_mul(4)),
+			);
+			<ReservedDmpWeightOverride<Test>>::put(Weight::from_parts(1_000_000, 1_000));
+		})
+		.add(2, || {
+			let message_weight =
+				DmpBaseWeight::get().saturating_add(DmpByteWeight::get().saturating_mul(100));
+			assert_eq!(dmp_weight_used(), message_weight);
+			// The handler only gets what is left of the reserved weight.
+			assert_eq!(
+				DMP_MAX_WEIGHT.with(|w| *w.borrow()),
+				Weight::from_parts(1_000_000, 1_000) - message_weight,
+			);
+		});
+}
+
 #[test]
 fn receive_hrmp() {
 	lazy_static::lazy_static! {
@@ -1483,6 +2113,61 @@ fn upgrade_version_checks_should_work() {
 	}
 }
 
+#[test]
+fn did_set_validation_data_works() {
+	new_test_ext().execute_with(|| {
+		assert!(!ParachainSystem::did_set_validation_data());
+	});
+
+	BlockTests::new().add(1, || {
+		assert!(ParachainSystem::did_set_validation_data());
+	});
+}
+
+#[test]
+fn max_pov_size_is_read_from_validation_data() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(ParachainSystem::max_pov_size(), None);
+	});
+
+	BlockTests::new()
+		.with_inherent_data(|_, _, data| {
+			data.validation_data.max_pov_size = 5 * 1024 * 1024;
+		})
+		.add(1, || {
+			assert_eq!(ParachainSystem::max_pov_size(), Some(5 * 1024 * 1024));
+		});
+}
+
+#[test]
+fn relay_parent_storage_root_is_read_from_validation_data() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(ParachainSystem::relay_parent_storage_root(), None);
+	});
+
+	BlockTests::new().add(123, || {
+		let (root, _) = cumulus_primitives_core::rpsr_digest::extract_relay_parent_storage_root(
+			&System::digest(),
+		)
+		.expect("relay parent storage root is deposited");
+		assert_eq!(ParachainSystem::relay_parent_storage_root(), Some(root));
+	});
+}
+
+#[test]
+fn relay_session_index_is_read_from_proof() {
+	BlockTests::new()
+		.with_relay_sproof_builder(|_, relay_block_num, sproof| {
+			sproof.current_session_index = relay_block_num * 10;
+		})
+		.add(1, || {
+			assert_eq!(ParachainSystem::relay_session_index(), Some(10));
+		})
+		.add(2, || {
+			assert_eq!(ParachainSystem::relay_session_index(), Some(20));
+		});
+}
+
 #[test]
 fn deposits_relay_parent_storage_root() {
 	BlockTests::new().add_with_post_test(
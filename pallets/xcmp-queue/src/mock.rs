@@ -106,11 +106,17 @@ impl cumulus_pallet_parachain_system::Config for Test {
 	type OnSystemEvent = ();
 	type SelfParaId = ();
 	type OutboundXcmpMessageSource = XcmpQueue;
+	type MaxPendingUpwardMessages = ConstU32<1024>;
 	type DmpMessageHandler = ();
 	type ReservedDmpWeight = ();
+	type DmpBaseWeight = ();
+	type DmpByteWeight = ();
 	type XcmpMessageHandler = XcmpQueue;
 	type ReservedXcmpWeight = ();
+	type ClearXcmpOverrideOnUpgrade = frame_support::traits::ConstBool<false>;
 	type CheckAssociatedRelayNumber = AnyRelayNumber;
+	type RejectRelayParentReplay = frame_support::traits::ConstBool<false>;
+	type ReportValidationDataMismatch = frame_support::traits::ConstBool<false>;
 	type ConsensusHook = cumulus_pallet_parachain_system::consensus_hook::ExpectParentIncluded;
 }
 
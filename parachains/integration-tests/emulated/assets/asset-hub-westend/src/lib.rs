@@ -47,9 +47,12 @@ pub use xcm::{
 	DoubleEncoded,
 };
 pub use xcm_emulator::{
-	assert_expected_events, bx, cumulus_pallet_dmp_queue, helpers::weight_within_threshold,
-	AccountId32Junction, Chain, ParaId, Parachain as Para, RelayChain as Relay, Test, TestArgs,
-	TestContext, TestExt, TestExternalities,
+	assert_expected_events, assert_pallet_xcm_attempt_complete, assert_weight_within, bx,
+	cumulus_pallet_dmp_queue,
+	helpers::{filter_events, weight_within_threshold},
+	AccountId32Junction, Chain, Network, NetworkComponent, ParaId, Parachain as Para,
+	ProcessMessageError, RelayChain as Relay, Storage, Test, TestArgs, TestContext, TestExt,
+	TestExternalities, XcmpMessageFormat,
 };
 
 pub const ASSET_ID: u32 = 1;
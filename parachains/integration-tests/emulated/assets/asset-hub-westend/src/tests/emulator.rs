@@ -0,0 +1,494 @@
+// Copyright Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::*;
+
+/// `read_storage` should decode the raw value stored under the given key
+#[test]
+fn read_storage_decodes_raw_storage_value() {
+	let key = [sp_core::twox_128(b"Balances"), sp_core::twox_128(b"TotalIssuance")].concat();
+
+	let total_issuance = AssetHubWestend::execute_with(|| {
+		pallet_balances::TotalIssuance::<<AssetHubWestend as Chain>::Runtime>::get()
+	});
+
+	assert!(total_issuance > 0);
+	assert_eq!(AssetHubWestend::read_storage::<Balance>(&key), Some(total_issuance));
+	assert_eq!(AssetHubWestend::read_storage::<Balance>(b"missing"), None);
+}
+
+fn xcmp_message(xcm: VersionedXcm<()>) -> Vec<u8> {
+	(XcmpMessageFormat::ConcatenatedVersionedXcm, xcm).encode()
+}
+
+fn clear_origin_xcmp_message() -> Vec<u8> {
+	xcmp_message(VersionedXcm::V3(Xcm(vec![ClearOrigin])))
+}
+
+fn send_horizontal_message_to_asset_hub(message: Vec<u8>, version: u32) {
+	let sender = PenpalWestendA::para_id();
+
+	PenpalWestendA::execute_with(|| {
+		<PenpalWestendA as NetworkComponent>::send_horizontal_messages(
+			AssetHubWestend::para_id().into(),
+			vec![(sender, 1, message)].into_iter(),
+			Some(version),
+		);
+	});
+}
+
+/// A horizontal message tagged with XCM v3 should be decoded by a Parachain expecting v3, where
+/// the bare `ClearOrigin` is stopped by the barrier
+#[test]
+fn send_v3_tagged_horizontal_message_reaches_barrier() {
+	send_horizontal_message_to_asset_hub(clear_origin_xcmp_message(), xcm::v3::VERSION);
+
+	AssetHubWestend::execute_with(|| {
+		type RuntimeEvent = <AssetHubWestend as Chain>::RuntimeEvent;
+
+		assert_expected_events!(
+			AssetHubWestend,
+			vec![
+				RuntimeEvent::XcmpQueue(cumulus_pallet_xcmp_queue::Event::Fail { error, .. }) => {
+					error: *error == XcmError::Barrier,
+				},
+			]
+		);
+	});
+}
+
+/// A horizontal message whose XCM version differs from its tag should be rejected
+#[test]
+#[should_panic = "is not encoded with XCM version"]
+fn send_mistagged_horizontal_message_fails() {
+	send_horizontal_message_to_asset_hub(clear_origin_xcmp_message(), xcm::v2::VERSION);
+}
+
+/// A horizontal message tagged with an XCM version the receiving Parachain doesn't expect should
+/// be rejected
+#[test]
+#[should_panic = "which expects Some(3)"]
+fn send_horizontal_message_tagged_with_unexpected_version_fails() {
+	let message =
+		xcmp_message(VersionedXcm::V2(xcm::v2::Xcm(vec![xcm::v2::Instruction::ClearOrigin])));
+	send_horizontal_message_to_asset_hub(message, xcm::v2::VERSION);
+}
+
+/// Storage layered with `override_genesis` should be present after the Network is reset, until
+/// it is cleared
+#[test]
+fn override_genesis_is_applied_on_reset() {
+	type Runtime = <AssetHubWestend as Chain>::Runtime;
+
+	let account = AssetHubWestend::account_id_of("Override");
+	let amount = ASSET_HUB_WESTEND_ED * 1_000;
+	let account_info = frame_system::AccountInfo {
+		providers: 1,
+		data: pallet_balances::AccountData { free: amount, ..Default::default() },
+		..Default::default()
+	};
+	let total_issuance = AssetHubWestend::ext_wrapper(|| {
+		<AssetHubWestend as AssetHubWestendPallet>::Balances::total_issuance()
+	});
+
+	// Keep the total issuance consistent with the added balance.
+	let mut overrides = Storage::default();
+	overrides
+		.top
+		.insert(frame_system::Account::<Runtime>::hashed_key_for(&account), account_info.encode());
+	overrides.top.insert(
+		pallet_balances::TotalIssuance::<Runtime>::hashed_key().to_vec(),
+		(total_issuance + amount).encode(),
+	);
+
+	AssetHubWestend::override_genesis(overrides);
+	WestendMockNet::reset();
+	assert_eq!(AssetHubWestend::account_data_of(account.clone()).free, amount);
+	assert_eq!(
+		AssetHubWestend::ext_wrapper(|| {
+			<AssetHubWestend as AssetHubWestendPallet>::Balances::total_issuance()
+		}),
+		total_issuance + amount
+	);
+
+	AssetHubWestend::clear_genesis_overrides();
+	WestendMockNet::reset();
+	assert_eq!(AssetHubWestend::account_data_of(account).free, 0);
+}
+
+/// `assert_weight_within!` should accept a weight inside the threshold band
+#[test]
+fn assert_weight_within_accepts_weight_in_band() {
+	assert_weight_within!(
+		Weight::from_parts(1_000_000, 10_000),
+		Weight::from_parts(1_050_000, 9_500),
+		(REF_TIME_THRESHOLD, PROOF_SIZE_THRESHOLD)
+	);
+}
+
+/// `assert_weight_within!` should reject a weight outside the threshold band
+#[test]
+#[should_panic = "is not within threshold of expected weight"]
+fn assert_weight_within_rejects_weight_out_of_band() {
+	assert_weight_within!(
+		Weight::from_parts(1_000_000, 10_000),
+		Weight::from_parts(2_000_000, 10_000),
+		(REF_TIME_THRESHOLD, PROOF_SIZE_THRESHOLD)
+	);
+}
+
+/// `filter_events` should extract the matching events of a chain in order
+#[test]
+fn filter_events_extracts_transfer_amounts() {
+	let amounts = vec![ASSET_HUB_WESTEND_ED * 10, ASSET_HUB_WESTEND_ED * 20];
+
+	AssetHubWestend::execute_with(|| {
+		type RuntimeEvent = <AssetHubWestend as Chain>::RuntimeEvent;
+
+		for amount in &amounts {
+			assert_ok!(<AssetHubWestend as AssetHubWestendPallet>::Balances::transfer_keep_alive(
+				<AssetHubWestend as Chain>::RuntimeOrigin::signed(AssetHubWestendSender::get()),
+				AssetHubWestendReceiver::get().into(),
+				*amount,
+			));
+		}
+
+		let transferred = filter_events::<AssetHubWestend, _>(|event| match event {
+			RuntimeEvent::Balances(pallet_balances::Event::Transfer { amount, .. }) =>
+				Some(*amount),
+			_ => None,
+		});
+
+		assert_eq!(transferred, amounts);
+	});
+}
+
+/// `relay_block_since_start` should count the relay blocks elapsed since the Network was
+/// initialized
+#[test]
+fn relay_block_since_start_counts_from_network_init() {
+	let offset = AssetHubWestend::relay_block_offset();
+	assert_eq!(AssetHubWestend::relay_block_since_start(), 0);
+
+	for elapsed in 1..=3 {
+		AssetHubWestend::execute_with(|| {});
+		assert_eq!(AssetHubWestend::relay_block_since_start(), elapsed);
+	}
+
+	assert_eq!(AssetHubWestend::relay_block_offset(), offset);
+	assert_eq!(WestendMockNet::relay_block_number(), offset + 3);
+}
+
+/// `process_messages_once` should leave the messages sent in response to the ones it processes
+/// to the next pass
+#[test]
+fn process_messages_once_steps_through_delivery() {
+	// Otherwise the relay chain doesn't know the XCM version to respond to Asset Hub with.
+	Westend::execute_with(|| {
+		assert_ok!(<Westend as WestendPallet>::XcmPallet::force_default_xcm_version(
+			<Westend as Chain>::RuntimeOrigin::root(),
+			Some(XCM_V3),
+		));
+	});
+
+	// First hop: Asset Hub subscribes to the XCM version of the relay chain...
+	let subscribe = VersionedXcm::<()>::V3(Xcm(vec![SubscribeVersion {
+		query_id: 0,
+		max_response_weight: Weight::zero(),
+	}]));
+	<AssetHubWestend as NetworkComponent>::send_upward_message(
+		AssetHubWestend::para_id().into(),
+		subscribe.encode(),
+	);
+	assert!(WestendMockNet::process_messages_once());
+
+	// ...second hop: the relay chain responds with a downward message
+	assert!(!WestendMockNet::process_messages_once());
+
+	AssetHubWestend::execute_with(|| {
+		type RuntimeEvent = <AssetHubWestend as Chain>::RuntimeEvent;
+
+		assert_expected_events!(
+			AssetHubWestend,
+			vec![
+				RuntimeEvent::DmpQueue(
+					cumulus_pallet_dmp_queue::Event::ExecutedDownward { .. }
+				) => {},
+			]
+		);
+	});
+}
+
+/// Relay storage set with `set_relay_storage` should be readable from the relay state proof
+#[test]
+fn set_relay_storage_is_included_in_relay_state_proof() {
+	let key = b"custom_relay_key".to_vec();
+	AssetHubWestend::set_relay_storage(key.clone(), 42u32.encode());
+
+	AssetHubWestend::execute_with(|| {
+		type ParachainSystem =
+			cumulus_pallet_parachain_system::Pallet<<AssetHubWestend as Chain>::Runtime>;
+
+		let vfp = ParachainSystem::validation_data().expect("validation data is set");
+		let relay_state_proof = cumulus_pallet_parachain_system::RelayChainStateProof::new(
+			AssetHubWestend::para_id(),
+			vfp.relay_parent_storage_root,
+			ParachainSystem::relay_state_proof().expect("relay state proof is set"),
+		)
+		.expect("relay state proof is valid");
+
+		assert_eq!(relay_state_proof.read_optional_entry::<u32>(&key).unwrap(), Some(42));
+	});
+}
+
+/// `block_number` should return the chain's block number, which advances after `execute_with`
+#[test]
+fn block_number_advances_after_execute_with() {
+	AssetHubWestend::execute_with(|| {});
+	let block_number = AssetHubWestend::block_number();
+
+	AssetHubWestend::execute_with(|| {
+		assert_eq!(<AssetHubWestend as Chain>::System::block_number(), block_number);
+	});
+
+	assert_eq!(AssetHubWestend::block_number(), block_number + 1);
+}
+
+/// `last_pallet_xcm_attempt` should record the outcome of a successful reserve transfer as
+/// complete
+#[test]
+fn last_pallet_xcm_attempt_is_complete_after_reserve_transfer() {
+	let destination = AssetHubWestend::sibling_location_of(PenpalWestendA::para_id());
+	let beneficiary: MultiLocation =
+		AccountId32Junction { network: None, id: PenpalWestendAReceiver::get().into() }.into();
+	let assets: MultiAssets = (Parent, ASSET_HUB_WESTEND_ED * 1000).into();
+
+	AssetHubWestend::execute_with(|| {
+		assert_ok!(
+			<AssetHubWestend as AssetHubWestendPallet>::PolkadotXcm::limited_reserve_transfer_assets(
+				<AssetHubWestend as Chain>::RuntimeOrigin::signed(AssetHubWestendSender::get()),
+				bx!(destination.into()),
+				bx!(beneficiary.into()),
+				bx!(assets.into()),
+				0,
+				WeightLimit::Unlimited,
+			)
+		);
+	});
+
+	assert!(matches!(AssetHubWestend::last_pallet_xcm_attempt(), Some(Outcome::Complete(_))));
+	assert_pallet_xcm_attempt_complete!(AssetHubWestend);
+}
+
+/// `skip_relay_blocks` should make the next parachain block build on the jumped relay parent
+#[test]
+fn skip_relay_blocks_jumps_relay_parent_number() {
+	AssetHubWestend::execute_with(|| {});
+	let relay_block_number = WestendMockNet::relay_block_number();
+
+	AssetHubWestend::skip_relay_blocks(3);
+	assert_eq!(WestendMockNet::relay_block_number(), relay_block_number + 3);
+	assert!(!WestendMockNet::has_unprocessed_messages());
+
+	AssetHubWestend::execute_with(|| {
+		type ParachainSystem =
+			cumulus_pallet_parachain_system::Pallet<<AssetHubWestend as Chain>::Runtime>;
+
+		let vfp = ParachainSystem::validation_data().expect("validation data is set");
+		assert_eq!(vfp.relay_parent_number, relay_block_number + 4);
+	});
+}
+
+/// `reset_messages_only` should clear the queued messages but keep the state of the chains
+#[test]
+fn reset_messages_only_keeps_chain_state() {
+	let receiver = AssetHubWestendReceiver::get();
+	let amount = ASSET_HUB_WESTEND_ED * 1000;
+
+	AssetHubWestend::execute_with(|| {
+		assert_ok!(<AssetHubWestend as AssetHubWestendPallet>::Balances::transfer_keep_alive(
+			<AssetHubWestend as Chain>::RuntimeOrigin::signed(AssetHubWestendSender::get()),
+			receiver.clone().into(),
+			amount,
+		));
+	});
+	let receiver_balance = AssetHubWestend::account_data_of(receiver.clone()).free;
+
+	<PenpalWestendA as NetworkComponent>::send_horizontal_messages(
+		AssetHubWestend::para_id().into(),
+		vec![(PenpalWestendA::para_id(), 1, clear_origin_xcmp_message())].into_iter(),
+		None,
+	);
+	assert!(WestendMockNet::has_unprocessed_messages());
+
+	WestendMockNet::reset_messages_only();
+
+	assert!(!WestendMockNet::has_unprocessed_messages());
+	assert_eq!(AssetHubWestend::account_data_of(receiver).free, receiver_balance);
+}
+
+fn executed_downward_count() -> usize {
+	type RuntimeEvent = <AssetHubWestend as Chain>::RuntimeEvent;
+
+	AssetHubWestend::events()
+		.into_iter()
+		.filter(|event| {
+			matches!(
+				event,
+				RuntimeEvent::DmpQueue(cumulus_pallet_dmp_queue::Event::ExecutedDownward { .. })
+			)
+		})
+		.count()
+}
+
+fn send_downward_message_to_asset_hub() {
+	let destination = Westend::child_location_of(AssetHubWestend::para_id()).into();
+	let xcm = VersionedXcm::from(Xcm(vec![
+		UnpaidExecution { weight_limit: Unlimited, check_origin: None },
+		ClearOrigin,
+	]));
+
+	assert_ok!(<Westend as WestendPallet>::XcmPallet::send(
+		<Westend as Chain>::RuntimeOrigin::root(),
+		bx!(destination),
+		bx!(xcm),
+	));
+}
+
+/// `reset_messages_only` should not make the relay chain deliver its downward messages again
+#[test]
+fn reset_messages_only_does_not_redeliver_downward_messages() {
+	Westend::execute_with(send_downward_message_to_asset_hub);
+	AssetHubWestend::execute_with(|| assert_eq!(executed_downward_count(), 1));
+
+	WestendMockNet::reset_messages_only();
+
+	// The relay chain doesn't hand the delivered message over again.
+	Westend::execute_with(|| {});
+	AssetHubWestend::execute_with(|| assert_eq!(executed_downward_count(), 0));
+}
+
+/// `reset_messages_only` should drop the downward messages still in the queues of the relay chain
+#[test]
+fn reset_messages_only_drops_undelivered_downward_messages() {
+	WestendMockNet::init();
+	Westend::ext_wrapper(send_downward_message_to_asset_hub);
+
+	WestendMockNet::reset_messages_only();
+
+	// The relay chain doesn't hand the dropped message over.
+	Westend::execute_with(|| {});
+	AssetHubWestend::execute_with(|| assert_eq!(executed_downward_count(), 0));
+}
+
+fn send_hrmp_message_from_penpal_to_asset_hub() {
+	let destination = PenpalWestendA::sibling_location_of(AssetHubWestend::para_id()).into();
+	let xcm = VersionedXcm::from(Xcm(vec![ClearOrigin]));
+
+	// The message goes through Penpal's XCMP queue, the emulator delivers it when the block ends.
+	PenpalWestendA::execute_with(|| {
+		assert_ok!(<PenpalWestendA as PenpalWestendAPallet>::PolkadotXcm::send(
+			<PenpalWestendA as Chain>::RuntimeOrigin::signed(PenpalWestendASender::get()),
+			bx!(destination),
+			bx!(xcm),
+		));
+	});
+}
+
+/// `assert_hrmp_delivered` should see horizontal messages delivered within the relay block
+/// they were sent at
+#[test]
+fn hrmp_message_is_delivered_without_relay_delay() {
+	send_hrmp_message_from_penpal_to_asset_hub();
+
+	AssetHubWestend::assert_hrmp_delivered(
+		PenpalWestendA::para_id(),
+		AssetHubWestend::para_id(),
+		0,
+	);
+}
+
+/// `assert_hrmp_delivered` should panic for a delay the message was not delivered with
+#[test]
+#[should_panic = "was delivered 1 relay blocks after it was sent"]
+fn hrmp_message_delivery_delay_mismatch_panics() {
+	send_hrmp_message_from_penpal_to_asset_hub();
+
+	AssetHubWestend::assert_hrmp_delivered(
+		PenpalWestendA::para_id(),
+		AssetHubWestend::para_id(),
+		1,
+	);
+}
+
+/// `last_ump_result` should record the error of processing a malformed upward message
+#[test]
+fn malformed_upward_message_result_is_recorded() {
+	WestendMockNet::init();
+	let msg = vec![0xff, 0x00, 0x01];
+
+	<AssetHubWestend as NetworkComponent>::send_upward_message(
+		AssetHubWestend::para_id().into(),
+		msg.clone(),
+	);
+	WestendMockNet::process_messages();
+
+	assert_eq!(
+		AssetHubWestend::last_ump_result(sp_core::blake2_256(&msg)),
+		Some(Err(ProcessMessageError::Corrupt))
+	);
+}
+
+/// `assert_issuance_conserved` should hold around a teleport when both sides are checked
+#[test]
+fn issuance_is_conserved_around_teleport() {
+	let amount = WESTEND_ED * 1000;
+	let args = relay_test_args(amount);
+
+	// Teleported value is moved into the check account of the relay chain, not burned.
+	fn westend_circulating_issuance() -> Balance {
+		Westend::ext_wrapper(|| {
+			type Balances = <Westend as WestendPallet>::Balances;
+			let check_account = <Westend as WestendPallet>::XcmPallet::check_account();
+			Balances::total_issuance() - Balances::free_balance(check_account)
+		})
+	}
+	fn asset_hub_westend_issuance() -> Balance {
+		AssetHubWestend::ext_wrapper(|| {
+			<AssetHubWestend as AssetHubWestendPallet>::Balances::total_issuance()
+		})
+	}
+
+	WestendMockNet::assert_issuance_conserved(
+		vec![
+			("Westend", westend_circulating_issuance as fn() -> Balance),
+			("AssetHubWestend", asset_hub_westend_issuance as fn() -> Balance),
+		],
+		ASSET_HUB_WESTEND_ED,
+		|| {
+			Westend::execute_with(|| {
+				assert_ok!(<Westend as WestendPallet>::XcmPallet::limited_teleport_assets(
+					<Westend as Chain>::RuntimeOrigin::signed(WestendSender::get()),
+					bx!(args.dest.into()),
+					bx!(args.beneficiary.into()),
+					bx!(args.assets.into()),
+					args.fee_asset_item,
+					args.weight_limit,
+				));
+			})
+		},
+	);
+}
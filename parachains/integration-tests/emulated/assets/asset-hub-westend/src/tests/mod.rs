@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
 
+mod emulator;
 mod reserve_transfer;
 mod send;
 mod set_xcm_versions;
@@ -548,10 +548,16 @@ impl cumulus_pallet_parachain_system::Config for Runtime {
 	type SelfParaId = parachain_info::Pallet<Runtime>;
 	type DmpMessageHandler = DmpQueue;
 	type ReservedDmpWeight = ReservedDmpWeight;
+	type DmpBaseWeight = ();
+	type DmpByteWeight = ();
 	type OutboundXcmpMessageSource = XcmpQueue;
+	type MaxPendingUpwardMessages = ConstU32<1024>;
 	type XcmpMessageHandler = XcmpQueue;
 	type ReservedXcmpWeight = ReservedXcmpWeight;
+	type ClearXcmpOverrideOnUpgrade = ConstBool<false>;
 	type CheckAssociatedRelayNumber = RelayNumberStrictlyIncreases;
+	type RejectRelayParentReplay = ConstBool<false>;
+	type ReportValidationDataMismatch = ConstBool<false>;
 	type ConsensusHook = cumulus_pallet_aura_ext::FixedVelocityConsensusHook<
 		Runtime,
 		RELAY_CHAIN_SLOT_DURATION_MILLIS,
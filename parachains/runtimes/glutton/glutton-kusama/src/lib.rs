@@ -174,11 +174,17 @@ impl cumulus_pallet_parachain_system::Config for Runtime {
 	type OnSystemEvent = ();
 	type SelfParaId = parachain_info::Pallet<Runtime>;
 	type OutboundXcmpMessageSource = ();
+	type MaxPendingUpwardMessages = frame_support::traits::ConstU32<1024>;
 	type DmpMessageHandler = cumulus_pallet_xcm::UnlimitedDmpExecution<Runtime>;
 	type ReservedDmpWeight = ReservedDmpWeight;
+	type DmpBaseWeight = ();
+	type DmpByteWeight = ();
 	type XcmpMessageHandler = ();
 	type ReservedXcmpWeight = ();
+	type ClearXcmpOverrideOnUpgrade = frame_support::traits::ConstBool<false>;
 	type CheckAssociatedRelayNumber = RelayNumberStrictlyIncreases;
+	type RejectRelayParentReplay = frame_support::traits::ConstBool<false>;
+	type ReportValidationDataMismatch = frame_support::traits::ConstBool<false>;
 	type ConsensusHook = cumulus_pallet_parachain_system::consensus_hook::ExpectParentIncluded;
 }
 
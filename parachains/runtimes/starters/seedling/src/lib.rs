@@ -170,11 +170,17 @@ impl cumulus_pallet_parachain_system::Config for Runtime {
 	type OnSystemEvent = cumulus_pallet_solo_to_para::Pallet<Runtime>;
 	type SelfParaId = parachain_info::Pallet<Runtime>;
 	type OutboundXcmpMessageSource = ();
+	type MaxPendingUpwardMessages = frame_support::traits::ConstU32<1024>;
 	type DmpMessageHandler = ();
 	type ReservedDmpWeight = ();
+	type DmpBaseWeight = ();
+	type DmpByteWeight = ();
 	type XcmpMessageHandler = ();
 	type ReservedXcmpWeight = ();
+	type ClearXcmpOverrideOnUpgrade = frame_support::traits::ConstBool<false>;
 	type CheckAssociatedRelayNumber = RelayNumberStrictlyIncreases;
+	type RejectRelayParentReplay = frame_support::traits::ConstBool<false>;
+	type ReportValidationDataMismatch = frame_support::traits::ConstBool<false>;
 	type ConsensusHook = cumulus_pallet_parachain_system::consensus_hook::ExpectParentIncluded;
 }
 
@@ -47,6 +47,16 @@ pub mod relay_chain {
 	pub use polkadot_primitives::*;
 }
 
+/// The storage key of the current session index on the relay chain.
+///
+/// This is `twox_128(b"Session") ++ twox_128(b"CurrentIndex")` and the value is expected to be
+/// decoded as a [`relay_chain::SessionIndex`]. The key is not part of
+/// [`relay_chain::well_known_keys`], so it is defined here.
+pub const RELAY_CURRENT_SESSION_INDEX_KEY: &[u8] = &[
+	0xce, 0xc5, 0x07, 0x0d, 0x60, 0x9d, 0xd3, 0x49, 0x7f, 0x72, 0xbd, 0xe0, 0x7f, 0xc9, 0x6b, 0xa0,
+	0x72, 0x76, 0x38, 0x00, 0xa3, 0x6a, 0x99, 0xfd, 0xfc, 0x7c, 0x10, 0xf6, 0x41, 0x5f, 0x6e, 0xe6,
+];
+
 /// An inbound HRMP message.
 pub type InboundHrmpMessage = polkadot_primitives::InboundHrmpMessage<relay_chain::BlockNumber>;
 
@@ -54,7 +64,7 @@ pub type InboundHrmpMessage = polkadot_primitives::InboundHrmpMessage<relay_chai
 pub type OutboundHrmpMessage = polkadot_primitives::OutboundHrmpMessage<ParaId>;
 
 /// Error description of a message send failure.
-#[derive(Eq, PartialEq, Copy, Clone, RuntimeDebug, Encode, Decode)]
+#[derive(Eq, PartialEq, Copy, Clone, RuntimeDebug, Encode, Decode, TypeInfo)]
 pub enum MessageSendError {
 	/// The dispatch queue is full.
 	QueueFull,
@@ -64,6 +74,8 @@ pub enum MessageSendError {
 	TooBig,
 	/// Some other error.
 	Other,
+	/// There are too many messages pending to be sent.
+	TooMany,
 }
 
 impl From<MessageSendError> for &'static str {
@@ -74,6 +86,7 @@ impl From<MessageSendError> for &'static str {
 			NoChannel => "NoChannel",
 			TooBig => "TooBig",
 			Other => "Other",
+			TooMany => "TooMany",
 		}
 	}
 }
@@ -101,8 +114,8 @@ pub trait GetChannelInfo {
 
 /// Something that should be called when sending an upward message.
 pub trait UpwardMessageSender {
-	/// Send the given UMP message; return the expected number of blocks before the message will
-	/// be dispatched or an error if the message cannot be sent.
+	/// Send the given UMP message; return the position of the message in the queue of messages
+	/// pending to be sent or an error if the message cannot be sent.
 	/// return the hash of the message sent
 	fn send_upward_message(msg: UpwardMessage) -> Result<(u32, XcmHash), MessageSendError>;
 }
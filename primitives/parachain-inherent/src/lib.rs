@@ -116,3 +116,21 @@ impl MessageQueueChain {
 		self.0
 	}
 }
+
+/// Compute the head of the DMP message queue chain starting at `prev` after appending `messages`
+/// in order.
+pub fn compute_dmq_mqc_head(prev: RelayHash, messages: &[InboundDownwardMessage]) -> RelayHash {
+	messages
+		.iter()
+		.fold(&mut MessageQueueChain(prev), |mqc, m| mqc.extend_downward(m))
+		.head()
+}
+
+/// Compute the head of an HRMP channel message queue chain starting at `prev` after appending
+/// `messages` in order.
+pub fn compute_hrmp_mqc_head(prev: RelayHash, messages: &[InboundHrmpMessage]) -> RelayHash {
+	messages
+		.iter()
+		.fold(&mut MessageQueueChain(prev), |mqc, m| mqc.extend_hrmp(m))
+		.head()
+}
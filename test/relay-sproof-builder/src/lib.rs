@@ -44,6 +44,7 @@ pub struct RelayStateSproofBuilder {
 	pub hrmp_channels: BTreeMap<relay_chain::HrmpChannelId, AbridgedHrmpChannel>,
 	pub current_slot: relay_chain::Slot,
 	pub current_epoch: u64,
+	pub current_session_index: relay_chain::SessionIndex,
 	pub randomness: relay_chain::Hash,
 	pub additional_key_values: Vec<(Vec<u8>, Vec<u8>)>,
 	pub included_para_head: Option<relay_chain::HeadData>,
@@ -76,6 +77,7 @@ impl Default for RelayStateSproofBuilder {
 			hrmp_channels: BTreeMap::new(),
 			current_slot: 0.into(),
 			current_epoch: 0u64,
+			current_session_index: 0,
 			randomness: relay_chain::Hash::default(),
 			additional_key_values: vec![],
 			included_para_head: None,
@@ -200,6 +202,10 @@ impl RelayStateSproofBuilder {
 				self.randomness.encode(),
 			);
 			insert(relay_chain::well_known_keys::CURRENT_SLOT.to_vec(), self.current_slot.encode());
+			insert(
+				cumulus_primitives_core::RELAY_CURRENT_SESSION_INDEX_KEY.to_vec(),
+				self.current_session_index.encode(),
+			);
 
 			for (key, value) in self.additional_key_values {
 				insert(key, value);
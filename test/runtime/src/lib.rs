@@ -281,11 +281,17 @@ impl cumulus_pallet_parachain_system::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 	type OnSystemEvent = ();
 	type OutboundXcmpMessageSource = ();
+	type MaxPendingUpwardMessages = ConstU32<1024>;
 	type DmpMessageHandler = ();
 	type ReservedDmpWeight = ();
+	type DmpBaseWeight = ();
+	type DmpByteWeight = ();
 	type XcmpMessageHandler = ();
 	type ReservedXcmpWeight = ();
+	type ClearXcmpOverrideOnUpgrade = frame_support::traits::ConstBool<false>;
 	type CheckAssociatedRelayNumber = cumulus_pallet_parachain_system::AnyRelayNumber;
+	type RejectRelayParentReplay = frame_support::traits::ConstBool<false>;
+	type ReportValidationDataMismatch = frame_support::traits::ConstBool<false>;
 	type ConsensusHook = cumulus_pallet_parachain_system::consensus_hook::RequireParentIncluded;
 }
 
@@ -56,13 +56,14 @@ pub use cumulus_pallet_xcmp_queue::{Config as XcmpQueueConfig, Pallet as XcmpQue
 pub use cumulus_primitives_core::{
 	self,
 	relay_chain::{BlockNumber as RelayBlockNumber, HeadData},
-	DmpMessageHandler, ParaId, PersistedValidationData, XcmpMessageHandler,
+	DmpMessageHandler, ParaId, PersistedValidationData, XcmpMessageFormat, XcmpMessageHandler,
 };
 pub use cumulus_primitives_parachain_inherent::ParachainInherentData;
 pub use cumulus_test_relay_sproof_builder::RelayStateSproofBuilder;
 pub use pallet_message_queue::{
 	Config as MessageQueueConfig, Event as MessageQueueEvent, Pallet as MessageQueuePallet,
 };
+pub use pallet_xcm;
 pub use parachain_info;
 pub use parachains_common::{AccountId, Balance, BlockNumber};
 pub use polkadot_primitives;
@@ -74,7 +75,7 @@ pub use polkadot_runtime_parachains::{
 // Polkadot
 pub use xcm::{
 	v3::prelude::{AccountId32 as AccountId32Junction, Parachain as ParachainJunction, *},
-	VersionedMultiAssets, VersionedMultiLocation,
+	Version as XcmVersion, VersionedMultiAssets, VersionedMultiLocation, VersionedXcm,
 };
 pub use xcm_executor::traits::ConvertLocation;
 
@@ -87,12 +88,20 @@ thread_local! {
 	#[allow(clippy::type_complexity)]
 	pub static DMP_DONE: RefCell<HashMap<String, VecDeque<(u32, RelayBlockNumber, Vec<u8>)>>>
 		= RefCell::new(HashMap::new());
-	/// Horizontal messages, each message is: `(to_para_id, [(from_para_id, relay_block_number, msg)])`
+	/// Horizontal messages, each message is: `(to_para_id, [(from_para_id, relay_block_number, msg)], xcm_version)`
 	#[allow(clippy::type_complexity)]
-	pub static HORIZONTAL_MESSAGES: RefCell<HashMap<String, VecDeque<(u32, Vec<(ParaId, RelayBlockNumber, Vec<u8>)>)>>>
+	pub static HORIZONTAL_MESSAGES: RefCell<HashMap<String, VecDeque<(u32, Vec<(ParaId, RelayBlockNumber, Vec<u8>)>, Option<XcmVersion>)>>>
+		= RefCell::new(HashMap::new());
+	/// Horizontal messages that already processed by parachains, each message is: `(from_para_id, to_para_id, sent_at, delivered_at)`
+	#[allow(clippy::type_complexity)]
+	pub static HRMP_DONE: RefCell<HashMap<String, VecDeque<(u32, u32, RelayBlockNumber, RelayBlockNumber)>>>
 		= RefCell::new(HashMap::new());
 	/// Upward messages, each message is: `(from_para_id, msg)`
 	pub static UPWARD_MESSAGES: RefCell<HashMap<String, VecDeque<(u32, Vec<u8>)>>> = RefCell::new(HashMap::new());
+	/// Results of the upward messages already processed by the relay chain, keyed by the `blake2_256` hash of the message
+	#[allow(clippy::type_complexity)]
+	pub static UMP_RESULTS: RefCell<HashMap<String, HashMap<[u8; 32], Result<bool, ProcessMessageError>>>>
+		= RefCell::new(HashMap::new());
 	/// Bridged messages, each message is: `BridgeMessage`
 	pub static BRIDGED_MESSAGES: RefCell<HashMap<String, VecDeque<BridgeMessage>>> = RefCell::new(HashMap::new());
 	/// Parachains Ids a the Network
@@ -101,6 +110,15 @@ thread_local! {
 	pub static INITIALIZED: RefCell<HashMap<String, bool>> = RefCell::new(HashMap::new());
 	/// Most recent `HeadData` of each parachain, encoded.
 	pub static LAST_HEAD: RefCell<HashMap<String, HashMap<u32, HeadData>>> = RefCell::new(HashMap::new());
+	/// Relay block number of each Network at the time it was initialized
+	pub static RELAY_BLOCK_OFFSET: RefCell<HashMap<String, u32>> = RefCell::new(HashMap::new());
+	/// Additional relay chain storage of each Network, included in the relay state proofs
+	#[allow(clippy::type_complexity)]
+	pub static RELAY_STORAGE: RefCell<HashMap<String, Vec<(Vec<u8>, Vec<u8>)>>> = RefCell::new(HashMap::new());
+	/// Genesis storage overrides of each chain, layered on top of its genesis
+	pub static GENESIS_OVERRIDES: RefCell<HashMap<String, Storage>> = RefCell::new(HashMap::new());
+	/// Outcome of the last XCM attempted through `pallet_xcm` on each chain that declares it
+	pub static PALLET_XCM_ATTEMPTS: RefCell<HashMap<String, Outcome>> = RefCell::new(HashMap::new());
 }
 
 pub trait CheckAssertion<Origin, Destination, Hops, Args>
@@ -134,18 +152,28 @@ where
 
 pub trait TestExt {
 	fn build_new_ext(storage: Storage) -> TestExternalities;
+	/// Build new externalities from the chain's genesis with `overrides` layered on top of it.
+	fn build_new_ext_with(overrides: Storage) -> TestExternalities;
 	fn new_ext() -> TestExternalities;
 	fn move_ext_out(id: &'static str);
 	fn move_ext_in(id: &'static str);
 	fn reset_ext();
 	fn execute_with<R>(execute: impl FnOnce() -> R) -> R;
 	fn ext_wrapper<R>(func: impl FnOnce() -> R) -> R;
+
+	/// Read and decode the raw storage value under `key` from this chain's externalities.
+	fn read_storage<V: Decode>(key: &[u8]) -> Option<V> {
+		Self::ext_wrapper(|| frame_support::storage::unhashed::get(key))
+	}
 }
 
 impl TestExt for () {
 	fn build_new_ext(_storage: Storage) -> TestExternalities {
 		TestExternalities::default()
 	}
+	fn build_new_ext_with(_overrides: Storage) -> TestExternalities {
+		TestExternalities::default()
+	}
 	fn new_ext() -> TestExternalities {
 		TestExternalities::default()
 	}
@@ -167,10 +195,24 @@ pub trait Network {
 	fn name() -> &'static str;
 	fn init();
 	fn reset();
+	/// Clear the queued messages of the Network, leaving the state of its chains intact.
+	///
+	/// The record of delivered messages is kept, so messages delivered before are neither
+	/// delivered again nor forgotten by e.g. `assert_hrmp_delivered`. The dropped downward
+	/// messages, including those still in the downward message queues of the relay chain, are
+	/// recorded as delivered so that the relay chain doesn't hand them over again.
+	fn reset_messages_only();
 	fn para_ids() -> Vec<u32>;
 	fn relay_block_number() -> u32;
 	fn set_relay_block_number(number: u32);
 	fn process_messages();
+	/// Process the queued messages in a single upward, horizontal, downward and bridged pass.
+	///
+	/// Unlike `process_messages`, the downward messages the relay chain sent while processing
+	/// upward messages are queued at the end of the pass, to be delivered by the next one.
+	///
+	/// Returns whether there are still unprocessed messages afterwards.
+	fn process_messages_once() -> bool;
 	fn has_unprocessed_messages() -> bool;
 	fn process_downward_messages();
 	fn process_horizontal_messages();
@@ -181,20 +223,69 @@ pub trait Network {
 		relay_parent_number: u32,
 		parent_head_data: HeadData,
 	) -> ParachainInherentData;
+
+	/// Run `execute` and assert that the sum of the values read by `currency_checks` differs by
+	/// at most `tolerance` before and after it, e.g. to account for fees.
+	///
+	/// Each check is a chain name together with a function reading its total issuance, or any
+	/// other balance that should be conserved.
+	///
+	/// Only transfers that keep the value within the checked chains conserve it, e.g. teleports
+	/// between them. A reserve transfer locks the value in a sovereign account while the
+	/// destination mints a derivative, so it isn't conserved across chains.
+	fn assert_issuance_conserved<R>(
+		currency_checks: Vec<(&'static str, fn() -> Balance)>,
+		tolerance: Balance,
+		execute: impl FnOnce() -> R,
+	) -> R {
+		let read = || {
+			currency_checks
+				.iter()
+				.map(|(chain, check)| (*chain, check()))
+				.collect::<Vec<_>>()
+		};
+		let total = |values: &[(&str, Balance)]| values.iter().map(|(_, v)| *v).sum::<Balance>();
+
+		let before = read();
+		let r = execute();
+		// Value still in flight would otherwise show up as a leak.
+		Self::process_messages();
+		let after = read();
+
+		assert!(
+			total(&before).abs_diff(total(&after)) <= tolerance,
+			"Issuance is not conserved within {}: {:?} before, {:?} after",
+			tolerance,
+			before,
+			after
+		);
+
+		r
+	}
 }
 
 pub trait NetworkComponent {
 	type Network: Network;
 
+	/// The current block number of this chain.
+	fn block_number() -> BlockNumber;
+
+	/// Queue horizontal messages for `to_para_id`.
+	///
+	/// When `version` is set, the receiving side asserts that every XCM in the messages is encoded
+	/// with that version, and that it is the version the receiving parachain expects, i.e. the
+	/// safe XCM version of the `PalletXcm` it declares.
 	fn send_horizontal_messages<I: Iterator<Item = (ParaId, RelayBlockNumber, Vec<u8>)>>(
 		to_para_id: u32,
 		iter: I,
+		version: Option<XcmVersion>,
 	) {
 		HORIZONTAL_MESSAGES.with(|b| {
-			b.borrow_mut()
-				.get_mut(Self::Network::name())
-				.unwrap()
-				.push_back((to_para_id, iter.collect()))
+			b.borrow_mut().get_mut(Self::Network::name()).unwrap().push_back((
+				to_para_id,
+				iter.collect(),
+				version,
+			))
 		});
 	}
 
@@ -223,6 +314,103 @@ pub trait NetworkComponent {
 		BRIDGED_MESSAGES
 			.with(|b| b.borrow_mut().get_mut(Self::Network::name()).unwrap().push_back(msg));
 	}
+
+	/// Process the Network's queued messages in a single pass, see
+	/// [`Network::process_messages_once`].
+	fn process_messages_once() -> bool {
+		Self::Network::process_messages_once()
+	}
+
+	/// Include `value` under `key` in the relay state proofs given to the Network's parachains.
+	fn set_relay_storage(key: Vec<u8>, value: Vec<u8>) {
+		RELAY_STORAGE.with(|b| {
+			let mut relay_storage = b.borrow_mut();
+			let entries = relay_storage.entry(Self::Network::name().to_string()).or_default();
+			entries.retain(|(k, _)| *k != key);
+			entries.push((key, value));
+		});
+	}
+
+	/// The result of processing the last upward message with the `blake2_256` hash `hash` on the
+	/// relay chain.
+	///
+	/// `Err(ProcessMessageError::Yield)` if the relay chain's message queue didn't run the message,
+	/// e.g. because it was moved to the overweight queue.
+	fn last_ump_result(hash: [u8; 32]) -> Option<Result<bool, ProcessMessageError>> {
+		UMP_RESULTS.with(|b| {
+			b.borrow()
+				.get(Self::Network::name())
+				.and_then(|results| results.get(&hash).copied())
+		})
+	}
+
+	/// Assert that a horizontal message from `from` was delivered to `to` exactly
+	/// `relay_blocks_delay` relay blocks after it was sent.
+	fn assert_hrmp_delivered(from: ParaId, to: ParaId, relay_blocks_delay: u32) {
+		let delivered = HRMP_DONE.with(|b| {
+			b.borrow().get(Self::Network::name()).map_or(false, |done| {
+				done.iter().any(|(from_para_id, to_para_id, sent_at, delivered_at)| {
+					*from_para_id == u32::from(from) &&
+						*to_para_id == u32::from(to) &&
+						delivered_at.saturating_sub(*sent_at) == relay_blocks_delay
+				})
+			})
+		});
+
+		assert!(
+			delivered,
+			"No horizontal message from {:?} to {:?} was delivered {} relay blocks after it was sent",
+			from, to, relay_blocks_delay
+		);
+	}
+
+	/// The relay block number at the time the Network was initialized.
+	fn relay_block_offset() -> u32 {
+		Self::Network::init();
+		RELAY_BLOCK_OFFSET
+			.with(|b| *b.borrow().get(Self::Network::name()).expect("network not initialized?"))
+	}
+
+	/// The number of relay blocks elapsed since the Network was initialized.
+	fn relay_block_since_start() -> u32 {
+		Self::Network::relay_block_number().saturating_sub(Self::relay_block_offset())
+	}
+
+	/// The outcome of the last XCM attempted through `pallet_xcm` on this chain.
+	///
+	/// This is only recorded for chains declaring their `PalletXcm` in `core`. The XCMs executed
+	/// by the message queues of the chain are not covered.
+	fn last_pallet_xcm_attempt() -> Option<Outcome> {
+		PALLET_XCM_ATTEMPTS.with(|b| b.borrow().get(type_name::<Self>()).cloned())
+	}
+
+	/// Advance the relay block number by `n`, modeling a gap in relay chain blocks.
+	///
+	/// No messages are delivered and no chain logic runs for the skipped blocks.
+	fn skip_relay_blocks(n: u32) {
+		Self::Network::init();
+		Self::Network::set_relay_block_number(Self::Network::relay_block_number() + n);
+	}
+
+	/// Layer `storage` on top of this chain's genesis.
+	///
+	/// The overrides are applied whenever the chain's externalities are built, so they take
+	/// effect on the next `Network::reset`. They stay in place until `clear_genesis_overrides`
+	/// is called, also for later tests running on the same thread.
+	fn override_genesis(storage: Storage) {
+		GENESIS_OVERRIDES.with(|b| {
+			let mut overrides = b.borrow_mut();
+			let chain_overrides = overrides.entry(type_name::<Self>().to_string()).or_default();
+			helpers::extend_storage(chain_overrides, storage);
+		});
+	}
+
+	/// Remove the storage layered on top of this chain's genesis with `override_genesis`.
+	///
+	/// Like the overrides, this takes effect on the next `Network::reset`.
+	fn clear_genesis_overrides() {
+		GENESIS_OVERRIDES.with(|b| b.borrow_mut().remove(type_name::<Self>()));
+	}
 }
 
 pub trait Chain: TestExt + NetworkComponent {
@@ -355,7 +543,7 @@ macro_rules! decl_test_relay_chains {
 				core = {
 					MessageProcessor: $mp:path,
 					SovereignAccountOf: $sovereign_acc_of:path,
-
+					$(PalletXcm: $relay_pallet_xcm:ty,)?
 				},
 				pallets = {
 					$($pallet_name:ident: $pallet_path:path,)*
@@ -406,6 +594,12 @@ macro_rules! decl_test_relay_chains {
 				}
 			}
 
+			impl $name {
+				fn note_pallet_xcm_attempt() {
+					$( $crate::helpers::note_pallet_xcm_attempt::<Self, _>($crate::PhantomData::<$relay_pallet_xcm>); )?
+				}
+			}
+
 			$crate::__impl_test_ext_for_relay_chain!($name, $genesis, $on_init, $api_version);
 			$crate::__impl_check_assertion!($name);
 		)+
@@ -431,7 +625,7 @@ macro_rules! __impl_test_ext_for_relay_chain {
 	(@impl $name:ident, $genesis:expr, $on_init:expr, $api_version:ident, $local_ext:ident, $global_ext:ident) => {
 		thread_local! {
 			pub static $local_ext: $crate::RefCell<$crate::TestExternalities>
-				= $crate::RefCell::new(<$name>::build_new_ext($genesis));
+				= $crate::RefCell::new(<$name>::build_new_ext_with($crate::helpers::genesis_overrides_of::<$name>()));
 		}
 
 		$crate::lazy_static! {
@@ -457,8 +651,14 @@ macro_rules! __impl_test_ext_for_relay_chain {
 				ext
 			}
 
+			fn build_new_ext_with(overrides: $crate::Storage) -> $crate::TestExternalities {
+				let mut storage = $genesis;
+				$crate::helpers::extend_storage(&mut storage, overrides);
+				<$name>::build_new_ext(storage)
+			}
+
 			fn new_ext() -> $crate::TestExternalities {
-				<$name>::build_new_ext($genesis)
+				<$name>::build_new_ext_with($crate::helpers::genesis_overrides_of::<$name>())
 			}
 
 			fn move_ext_out(id: &'static str) {
@@ -509,7 +709,7 @@ macro_rules! __impl_test_ext_for_relay_chain {
 			}
 
 			fn reset_ext() {
-				$local_ext.with(|v| *v.borrow_mut() = <$name>::build_new_ext($genesis));
+				$local_ext.with(|v| *v.borrow_mut() = <$name>::new_ext());
 			}
 
 			fn execute_with<R>(execute: impl FnOnce() -> R) -> R {
@@ -523,22 +723,12 @@ macro_rules! __impl_test_ext_for_relay_chain {
 				// Send messages if needed
 				$local_ext.with(|v| {
 					v.borrow_mut().execute_with(|| {
-						use $crate::polkadot_primitives::runtime_api::runtime_decl_for_parachain_host::$api_version;
-
-						//TODO: mark sent count & filter out sent msg
-						for para_id in<$name as NetworkComponent>::Network::para_ids() {
-							// downward messages
-							let downward_messages = <Self as Chain>::Runtime::dmq_contents(para_id.into())
-								.into_iter()
-								.map(|inbound| (inbound.sent_at, inbound.msg));
-							if downward_messages.len() == 0 {
-								continue;
-							}
-							<$name>::send_downward_messages(para_id, downward_messages.into_iter());
+						// Note: no need to handle horizontal messages, as the
+						// simulator directly sends them to dest (not relayed).
+						<$name>::send_undelivered_downward_messages();
 
-							// Note: no need to handle horizontal messages, as the
-							// simulator directly sends them to dest (not relayed).
-						}
+						// record the outcome of attempted XCMs
+						<$name>::note_pallet_xcm_attempt();
 
 						// log events
 						Self::events().iter().for_each(|event| {
@@ -563,6 +753,32 @@ macro_rules! __impl_test_ext_for_relay_chain {
 				})
 			}
 		}
+
+		impl $name {
+			/// Queue the messages in the downward message queues of the relay chain that were not
+			/// delivered yet.
+			///
+			/// This must be called within the externalities of the relay chain.
+			pub fn send_undelivered_downward_messages() {
+				use $crate::{Chain, NetworkComponent, Network};
+				use $crate::polkadot_primitives::runtime_api::runtime_decl_for_parachain_host::$api_version;
+
+				let network_name = <$name as NetworkComponent>::Network::name();
+				for para_id in <$name as NetworkComponent>::Network::para_ids() {
+					let downward_messages = <Self as Chain>::Runtime::dmq_contents(para_id.into())
+						.into_iter()
+						.map(|inbound| (inbound.sent_at, inbound.msg))
+						.filter(|(sent_at, msg)| !$crate::DMP_DONE.with(|b| {
+							b.borrow().get(network_name).map_or(false, |done| done.contains(&(para_id, *sent_at, msg.clone())))
+						}))
+						.collect::<Vec<_>>();
+					if downward_messages.is_empty() {
+						continue;
+					}
+					<$name>::send_downward_messages(para_id, downward_messages.into_iter());
+				}
+			}
+		}
 	};
 }
 
@@ -580,6 +796,7 @@ macro_rules! decl_test_parachains {
 					DmpMessageHandler: $dmp_message_handler:path,
 					LocationToAccountId: $location_to_account:path,
 					ParachainInfo: $parachain_info:path,
+					$(PalletXcm: $pallet_xcm:ty,)?
 				},
 				pallets = {
 					$($pallet_name:ident: $pallet_path:path,)*
@@ -658,6 +875,20 @@ macro_rules! decl_test_parachains {
 				}
 			}
 
+			impl $name {
+				fn note_pallet_xcm_attempt() {
+					$( $crate::helpers::note_pallet_xcm_attempt::<Self, _>($crate::PhantomData::<$pallet_xcm>); )?
+				}
+
+				/// The XCM version this chain expects tagged horizontal messages in, i.e. the safe
+				/// XCM version of its `PalletXcm`. `None` if it doesn't declare one.
+				///
+				/// This must be called within the externalities of this chain.
+				pub fn expected_xcm_version() -> Option<$crate::XcmVersion> {
+					None $( .or_else(|| $crate::helpers::safe_xcm_version_of($crate::PhantomData::<$pallet_xcm>)) )?
+				}
+			}
+
 			$crate::__impl_test_ext_for_parachain!($name, $genesis, $on_init);
 			$crate::__impl_check_assertion!($name);
 		)+
@@ -676,7 +907,7 @@ macro_rules! __impl_test_ext_for_parachain {
 	(@impl $name:ident, $genesis:expr, $on_init:expr, $local_ext:ident, $global_ext:ident) => {
 		thread_local! {
 			pub static $local_ext: $crate::RefCell<$crate::TestExternalities>
-				= $crate::RefCell::new(<$name>::build_new_ext($genesis));
+				= $crate::RefCell::new(<$name>::build_new_ext_with($crate::helpers::genesis_overrides_of::<$name>()));
 		}
 
 		$crate::lazy_static! {
@@ -702,8 +933,14 @@ macro_rules! __impl_test_ext_for_parachain {
 				ext
 			}
 
+			fn build_new_ext_with(overrides: $crate::Storage) -> $crate::TestExternalities {
+				let mut storage = $genesis;
+				$crate::helpers::extend_storage(&mut storage, overrides);
+				<$name>::build_new_ext(storage)
+			}
+
 			fn new_ext() -> $crate::TestExternalities {
-				<$name>::build_new_ext($genesis)
+				<$name>::build_new_ext_with($crate::helpers::genesis_overrides_of::<$name>())
 			}
 
 			fn move_ext_out(id: &'static str) {
@@ -754,7 +991,7 @@ macro_rules! __impl_test_ext_for_parachain {
 			}
 
 			fn reset_ext() {
-				$local_ext.with(|v| *v.borrow_mut() = <$name>::build_new_ext($genesis));
+				$local_ext.with(|v| *v.borrow_mut() = <$name>::new_ext());
 			}
 
 			fn execute_with<R>(execute: impl FnOnce() -> R) -> R {
@@ -836,6 +1073,7 @@ macro_rules! __impl_test_ext_for_parachain {
 							<$name>::send_horizontal_messages(
 								msg.recipient.into(),
 								vec![(para_id.into(), relay_block_number, msg.data)].into_iter(),
+								None,
 							);
 						}
 
@@ -849,6 +1087,9 @@ macro_rules! __impl_test_ext_for_parachain {
 							<$name>::send_bridged_messages(msg);
 						}
 
+						// record the outcome of attempted XCMs
+						<$name>::note_pallet_xcm_attempt();
+
 						// log events
 						Self::events().iter().for_each(|event| {
 							$crate::log::debug!(target: concat!("events::", stringify!($name)), "{:?}", event);
@@ -913,26 +1154,57 @@ macro_rules! decl_test_networks {
 					$crate::INITIALIZED.with(|b| b.borrow_mut().remove(Self::name()));
 					$crate::DOWNWARD_MESSAGES.with(|b| b.borrow_mut().remove(Self::name()));
 					$crate::DMP_DONE.with(|b| b.borrow_mut().remove(Self::name()));
+					$crate::HRMP_DONE.with(|b| b.borrow_mut().remove(Self::name()));
 					$crate::UPWARD_MESSAGES.with(|b| b.borrow_mut().remove(Self::name()));
+					$crate::UMP_RESULTS.with(|b| b.borrow_mut().remove(Self::name()));
 					$crate::HORIZONTAL_MESSAGES.with(|b| b.borrow_mut().remove(Self::name()));
 					$crate::BRIDGED_MESSAGES.with(|b| b.borrow_mut().remove(Self::name()));
 					$crate::LAST_HEAD.with(|b| b.borrow_mut().remove(Self::name()));
+					$crate::RELAY_BLOCK_OFFSET.with(|b| b.borrow_mut().remove(Self::name()));
+					$crate::RELAY_STORAGE.with(|b| b.borrow_mut().remove(Self::name()));
+					$crate::PALLET_XCM_ATTEMPTS.with(|b| {
+						let mut outcomes = b.borrow_mut();
+						outcomes.remove($crate::type_name::<$relay_chain>());
+						$( outcomes.remove($crate::type_name::<$parachain>()); )*
+					});
 
 					<$relay_chain>::reset_ext();
 					$( <$parachain>::reset_ext(); )*
 				}
 
+				fn reset_messages_only() {
+					Self::init();
+
+					// The relay chain hands over every downward message not in `DMP_DONE`, so the
+					// dropped ones are recorded there, including those still only in its queues.
+					<$relay_chain>::ext_wrapper(<$relay_chain>::send_undelivered_downward_messages);
+					let downward_messages = $crate::DOWNWARD_MESSAGES.with(|b| {
+						std::mem::take(b.borrow_mut().get_mut(Self::name()).unwrap())
+					});
+					$crate::DMP_DONE.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().extend(
+						downward_messages.into_iter().flat_map(|(to_para_id, messages)| {
+							messages.into_iter().map(move |(sent_at, msg)| (to_para_id, sent_at, msg))
+						})
+					));
+					$crate::UPWARD_MESSAGES.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().clear());
+					$crate::HORIZONTAL_MESSAGES.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().clear());
+					$crate::BRIDGED_MESSAGES.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().clear());
+				}
+
 				fn init() {
 					// If Network has not been initialized yet, it gets initialized
 					if $crate::INITIALIZED.with(|b| b.borrow_mut().get(Self::name()).is_none()) {
 						$crate::INITIALIZED.with(|b| b.borrow_mut().insert(Self::name().to_string(), true));
 						$crate::DOWNWARD_MESSAGES.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::VecDeque::new()));
 						$crate::DMP_DONE.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::VecDeque::new()));
+						$crate::HRMP_DONE.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::VecDeque::new()));
 						$crate::UPWARD_MESSAGES.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::VecDeque::new()));
+						$crate::UMP_RESULTS.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::HashMap::new()));
 						$crate::HORIZONTAL_MESSAGES.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::VecDeque::new()));
 						$crate::BRIDGED_MESSAGES.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::VecDeque::new()));
 						$crate::PARA_IDS.with(|b| b.borrow_mut().insert(Self::name().to_string(), Self::para_ids()));
 						$crate::LAST_HEAD.with(|b| b.borrow_mut().insert(Self::name().to_string(), $crate::HashMap::new()));
+						$crate::RELAY_BLOCK_OFFSET.with(|b| b.borrow_mut().insert(Self::name().to_string(), Self::relay_block_number()));
 
 						$( <$parachain>::init(); )*
 					}
@@ -965,6 +1237,19 @@ macro_rules! decl_test_networks {
 					}
 				}
 
+				fn process_messages_once() -> bool {
+					Self::process_upward_messages();
+					Self::process_horizontal_messages();
+					Self::process_downward_messages();
+					Self::process_bridged_messages();
+
+					// The relay chain may have sent downward messages while processing the above,
+					// e.g. in response to an upward message. They are left to the next pass.
+					<$relay_chain>::ext_wrapper(<$relay_chain>::send_undelivered_downward_messages);
+
+					Self::has_unprocessed_messages()
+				}
+
 				fn has_unprocessed_messages() -> bool {
 					$crate::DOWNWARD_MESSAGES.with(|b| !b.borrow_mut().get_mut(Self::name()).unwrap().is_empty())
 					|| $crate::HORIZONTAL_MESSAGES.with(|b| !b.borrow_mut().get_mut(Self::name()).unwrap().is_empty())
@@ -1008,16 +1293,31 @@ macro_rules! decl_test_networks {
 				fn process_horizontal_messages() {
 					use $crate::{XcmpMessageHandler, Bounded};
 
-					while let Some((to_para_id, messages))
+					while let Some((to_para_id, messages, version))
 						= $crate::HORIZONTAL_MESSAGES.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().pop_front()) {
+						if let Some(version) = version {
+							$crate::helpers::assert_xcmp_encoded_with_version(&messages, version);
+						}
 						let iter = messages.iter().map(|(p, b, m)| (*p, *b, &m[..])).collect::<Vec<_>>().into_iter();
 						$(
 							let para_id: u32 = <$parachain>::para_id().into();
 
 							if $crate::PARA_IDS.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().contains(&to_para_id)) && para_id == to_para_id {
+								if let Some(version) = version {
+									let expected = <$parachain>::ext_wrapper(<$parachain>::expected_xcm_version);
+									assert_eq!(
+										expected, Some(version),
+										"XCMP messages tagged with XCM version {} sent to {}, which expects {:?}",
+										version, stringify!($parachain), expected,
+									);
+								}
 								<$parachain>::ext_wrapper(|| {
 									<$parachain as Parachain>::XcmpMessageHandler::handle_xcmp_messages(iter.clone(), $crate::Weight::max_value());
 								});
+								let delivered_at = Self::relay_block_number();
+								$crate::HRMP_DONE.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().extend(
+									messages.iter().map(|(from_para_id, sent_at, _)| ((*from_para_id).into(), to_para_id, *sent_at, delivered_at))
+								));
 								$crate::log::debug!(target: concat!("hrmp::", stringify!($name)) , "HRMP messages processed {:?} to para_id {:?}", &messages, &to_para_id);
 							}
 						)*
@@ -1029,14 +1329,15 @@ macro_rules! decl_test_networks {
 					use sp_core::Encode;
 					while let Some((from_para_id, msg)) = $crate::UPWARD_MESSAGES.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().pop_front()) {
 						let mut weight_meter = WeightMeter::max_limit();
-						<$relay_chain>::ext_wrapper(|| {
-							let _ =  <$relay_chain as RelayChain>::MessageProcessor::process_message(
+						let result = <$relay_chain>::ext_wrapper(|| {
+							<$relay_chain as RelayChain>::MessageProcessor::process_message(
 								&msg[..],
 								from_para_id.into(),
 								&mut weight_meter,
 								&mut msg.using_encoded(sp_core::blake2_256),
-							);
+							)
 						});
+						$crate::UMP_RESULTS.with(|b| b.borrow_mut().get_mut(Self::name()).unwrap().insert(sp_core::blake2_256(&msg), result));
 						$crate::log::debug!(target: concat!("ump::", stringify!($name)) , "Upward message processed {:?} from para_id {:?}", &msg, &from_para_id);
 					}
 				}
@@ -1072,6 +1373,7 @@ macro_rules! decl_test_networks {
 
 					let mut sproof = $crate::RelayStateSproofBuilder::default();
 					sproof.para_id = para_id.into();
+					sproof.additional_key_values = $crate::RELAY_STORAGE.with(|b| b.borrow().get(Self::name()).cloned().unwrap_or_default());
 
 					// egress channel
 					let e_index = sproof.hrmp_egress_channel_index.get_or_insert_with(Vec::new);
@@ -1117,11 +1419,23 @@ macro_rules! decl_test_networks {
 
 			impl $crate::NetworkComponent for $relay_chain {
 				type Network = $name;
+
+				fn block_number() -> $crate::BlockNumber {
+					<Self as $crate::TestExt>::ext_wrapper(|| {
+						$crate::SystemPallet::<<Self as $crate::Chain>::Runtime>::block_number().into()
+					})
+				}
 			}
 
 			$(
 				impl $crate::NetworkComponent for $parachain {
 					type Network = $name;
+
+					fn block_number() -> $crate::BlockNumber {
+						<Self as $crate::TestExt>::ext_wrapper(|| {
+							$crate::SystemPallet::<<Self as $crate::Chain>::Runtime>::block_number().into()
+						})
+					}
 				}
 			)*
 		)+
@@ -1265,6 +1579,46 @@ macro_rules! assert_expected_events {
 	}
 }
 
+#[macro_export]
+macro_rules! assert_pallet_xcm_attempt_complete {
+	( $chain:ident ) => {
+		match <$chain as $crate::NetworkComponent>::last_pallet_xcm_attempt() {
+			Some($crate::Outcome::Complete(_)) => {},
+			outcome => panic!(
+				"{}: expected the last XCM attempted through pallet_xcm to be complete, got {:?}",
+				stringify!($chain),
+				outcome
+			),
+		}
+	};
+}
+
+#[macro_export]
+macro_rules! assert_weight_within {
+	( $expected:expr, $actual:expr, ($threshold_time:expr, $threshold_size:expr) $(,)? ) => {{
+		let expected: $crate::Weight = $expected;
+		let actual: $crate::Weight = $actual;
+
+		if !$crate::helpers::weight_within_threshold(
+			($threshold_time, $threshold_size),
+			expected,
+			actual,
+		) {
+			panic!(
+				"weight {:?} is not within threshold of expected weight {:?}:\n - ref_time: expected {} ± {}%, got {}\n - proof_size: expected {} ± {}%, got {}",
+				actual,
+				expected,
+				expected.ref_time(),
+				$threshold_time,
+				actual.ref_time(),
+				expected.proof_size(),
+				$threshold_size,
+				actual.proof_size(),
+			)
+		}
+	}};
+}
+
 #[macro_export]
 macro_rules! bx {
 	($e:expr) => {
@@ -1291,6 +1645,7 @@ impl<T> ProcessMessage for DefaultMessageProcessor<T>
 where
 	T: Chain + RelayChain,
 	T::Runtime: MessageQueueConfig,
+	T::RuntimeEvent: TryInto<MessageQueueEvent<T::Runtime>>,
 	<<T::Runtime as MessageQueueConfig>::MessageProcessor as ProcessMessage>::Origin:
 		PartialEq<AggregateMessageOrigin>,
 	MessageQueuePallet<T::Runtime>: EnqueueMessage<AggregateMessageOrigin> + ServiceQueues,
@@ -1309,7 +1664,22 @@ where
 		);
 		MessageQueuePallet::<T::Runtime>::service_queues(Weight::MAX);
 
-		Ok(true)
+		// Report how the message queue processed the message. It may also not have run it yet,
+		// e.g. when it was moved to the overweight queue.
+		let id = sp_core::blake2_256(msg);
+		T::events()
+			.into_iter()
+			.rev()
+			.find_map(|event| match event.try_into() {
+				Ok(MessageQueueEvent::Processed { id: processed, success, .. })
+					if processed == id =>
+					Some(Ok(success)),
+				Ok(MessageQueueEvent::ProcessingFailed { id: failed, error, .. })
+					if failed == id =>
+					Some(Err(error)),
+				_ => None,
+			})
+			.unwrap_or(Err(ProcessMessageError::Yield))
 	}
 }
 
@@ -1443,6 +1813,96 @@ pub mod helpers {
 		ref_time_within && proof_size_within
 	}
 
+	/// Assert that every XCM carried by the given XCMP messages is encoded with `version`.
+	///
+	/// `version` must be known to the `xcm` crate of the emulator.
+	pub fn assert_xcmp_encoded_with_version(
+		messages: &[(ParaId, RelayBlockNumber, Vec<u8>)],
+		version: XcmVersion,
+	) {
+		assert!(version <= xcm::latest::VERSION, "XCM version {} is unknown", version);
+
+		for (sender, _, data) in messages {
+			let mut data = &data[..];
+			let format = XcmpMessageFormat::decode(&mut data).expect("invalid XCMP message format");
+
+			if let XcmpMessageFormat::ConcatenatedVersionedXcm = format {
+				while !data.is_empty() {
+					// The first byte of an encoded `VersionedXcm` is its version.
+					assert_eq!(
+						data[0] as XcmVersion, version,
+						"XCMP message from {:?} is not encoded with XCM version {}",
+						sender, version
+					);
+					VersionedXcm::<()>::decode(&mut data).expect("invalid versioned XCM");
+				}
+			}
+		}
+	}
+
+	/// The safe XCM version of the `pallet_xcm` given by `_pallet`, i.e. the version it encodes
+	/// XCMs with when the destination's version is unknown.
+	///
+	/// This must be called within the externalities of the chain.
+	pub fn safe_xcm_version_of<T: pallet_xcm::Config>(
+		_pallet: PhantomData<pallet_xcm::Pallet<T>>,
+	) -> Option<XcmVersion> {
+		use frame_support::traits::PalletInfoAccess;
+
+		// `SafeXcmVersion` is private to `pallet_xcm`.
+		let key = [
+			sp_core::twox_128(pallet_xcm::Pallet::<T>::name().as_bytes()),
+			sp_core::twox_128(b"SafeXcmVersion"),
+		]
+		.concat();
+		frame_support::storage::unhashed::get(&key)
+	}
+
+	/// Collect the values `matcher` extracts from the events of chain `C`, in order.
+	///
+	/// Events that `matcher` maps to `None` are skipped.
+	pub fn filter_events<C: Chain, T>(
+		matcher: impl Fn(&<C as Chain>::RuntimeEvent) -> Option<T>,
+	) -> Vec<T> {
+		C::events().iter().filter_map(matcher).collect()
+	}
+
+	/// Record the outcome of the last `pallet_xcm::Event::Attempted` among the events of chain `C`,
+	/// whose `pallet_xcm` is given by `_pallet`.
+	pub fn note_pallet_xcm_attempt<C, T>(_pallet: PhantomData<pallet_xcm::Pallet<T>>)
+	where
+		C: Chain,
+		T: pallet_xcm::Config,
+		C::RuntimeEvent: TryInto<pallet_xcm::Event<T>>,
+	{
+		let outcome = C::events().into_iter().rev().find_map(|event| match event.try_into() {
+			Ok(pallet_xcm::Event::Attempted { outcome }) => Some(outcome),
+			_ => None,
+		});
+
+		if let Some(outcome) = outcome {
+			PALLET_XCM_ATTEMPTS
+				.with(|b| b.borrow_mut().insert(type_name::<C>().to_string(), outcome));
+		}
+	}
+
+	/// Layer `overrides` on top of `storage`, replacing values stored under the same keys.
+	pub fn extend_storage(storage: &mut Storage, overrides: Storage) {
+		storage.top.extend(overrides.top);
+		for (key, child) in overrides.children_default {
+			if let Some(existing) = storage.children_default.get_mut(&key) {
+				existing.data.extend(child.data);
+			} else {
+				storage.children_default.insert(key, child);
+			}
+		}
+	}
+
+	/// Genesis storage overrides registered for `T` with `NetworkComponent::override_genesis`.
+	pub fn genesis_overrides_of<T>() -> Storage {
+		GENESIS_OVERRIDES.with(|b| b.borrow().get(type_name::<T>()).cloned().unwrap_or_default())
+	}
+
 	/// Helper function to generate an account ID from seed.
 	pub fn get_account_id_from_seed<TPublic: sp_core::Public>(seed: &str) -> AccountId
 	where